@@ -1,7 +1,9 @@
 import 'dart:async';
 import 'dart:convert';
+import 'package:flutter/foundation.dart';
 import 'package:flutter/material.dart';
 import 'package:flutter/services.dart';
+import 'package:connectivity_plus/connectivity_plus.dart';
 import 'package:path_provider/path_provider.dart';
 import 'package:shared_preferences/shared_preferences.dart';
 import '../src/rust/node.dart' as rust;
@@ -35,13 +37,108 @@ class NodeManager extends ChangeNotifier {
   Map<String, int> _unreadCounts = {};
   String? activeChatPeerId;
   final Set<String> _onlinePeers = {};
-  bool isPeerOnline(String peerId) => _onlinePeers.contains(peerId);
+  // Invisible peers are connected but shown as offline
+  bool isPeerOnline(String peerId) =>
+      _onlinePeers.contains(peerId) && _peerStatus[peerId] != "INVISIBLE";
+  // Status (ONLINE, AWAY, DND, INVISIBLE) and status line of other peers
+  final Map<String, String> _peerStatus = {};
+  final Map<String, String> _peerStatusText = {};
+  String peerStatus(String peerId) => isPeerOnline(peerId) ? (_peerStatus[peerId] ?? "ONLINE") : "OFFLINE";
+  String peerStatusText(String peerId) => _peerStatusText[peerId] ?? "";
+  // How an online peer was found (MDNS, KADEMLIA, GOSSIP, CONNECTION, PRESENCE)
+  // and why an offline one left (TIMEOUT, CLOSED, MDNS_EXPIRED, UNROUTABLE, BLOCKED,
+  // UNRESPONSIVE, FOREIGN)
+  final Map<String, String> _discoverySource = {};
+  final Map<String, String> _departureReason = {};
+  String? discoverySource(String peerId) => _discoverySource[peerId];
+  String? departureReason(String peerId) => _departureReason[peerId];
+  // When offline contacts were last seen online (unix seconds), kept by the node across restarts
+  final Map<String, int> _lastSeen = {};
+  DateTime? lastSeen(String peerId) {
+    final secs = _lastSeen[peerId] ?? rust.getLastSeen(peerId: peerId)?.toInt();
+    if (secs == null) return null;
+    _lastSeen[peerId] = secs;
+    return DateTime.fromMillisecondsSinceEpoch(secs * 1000);
+  }
+
+  // Our own status as chosen by the user, AWAY is applied automatically while in the background
+  String _status = "ONLINE";
+  String get status => _status;
+  String _statusText = "";
+  String get statusText => _statusText;
+  bool _inBackground = false;
+  // Mobile apps that stay in the background this long only keep the relay connection
+  static const _suspendAfter = Duration(minutes: 5);
+  Timer? _suspendTimer;
+
+  // Incoming messages worth alerting about: not in the open chat and not in Do Not Disturb
+  final StreamController<Message> _alerts = StreamController.broadcast();
+  Stream<Message> get alerts => _alerts.stream;
+  // Ping round trip in ms and failed pings (in a row, in total) of online peers
+  final Map<String, int> _peerRtt = {};
+  final Map<String, List<int>> _pingFailures = {};
+  int? peerRtt(String peerId) => _peerRtt[peerId];
+  // How connected peers are reached (RELAYED, HOLE_PUNCHING, DIRECT, FAILED)
+  final Map<String, String> _connectionPath = {};
+  String? connectionPath(String peerId) {
+    final path = _connectionPath[peerId] ?? rust.getConnectionPath(peerId: peerId);
+    return path.isEmpty ? null : path;
+  }
+  int pingFailures(String peerId) => _pingFailures[peerId]?[1] ?? 0;
+  // Hole punching attempts, successes, failures and average time (total, by_local_nat, by_transport)
+  Map<String, dynamic> holePunchStats() {
+    try {
+      return json.decode(rust.getHolePunchStats());
+    } catch (e) {
+      return {};
+    }
+  }
+  // Blocked on the Rust side: connections refused and messages dropped
+  final Set<String> _blockedPeers = {};
+  List<String> get blockedPeers => _blockedPeers.toList();
+  bool isPeerBlocked(String peerId) => _blockedPeers.contains(peerId);
+  // Rendezvous rooms joined on this device and the peers found in each
+  final Set<String> _rooms = {};
+  List<String> get rooms => _rooms.toList();
+  final Map<String, Set<String>> _roomMembers = {};
+  List<String> roomMembers(String room) => _roomMembers[room]?.toList() ?? [];
+  bool _roomsRestored = false;
+
+  // Relay / bootstrap connectivity (CONNECTING, CONNECTED, RECONNECTING)
+  String _connectivity = "CONNECTING";
+  String get connectivity => _connectivity;
+
+  // Reachability detected by AutoNAT (PUBLIC, PRIVATE, UNKNOWN)
+  String _natStatus = "UNKNOWN";
+  String get natStatus => _natStatus;
+
+  // UPnP port mapping (DISABLED, SEARCHING, MAPPED, FAILED)
+  String _upnpStatus = "DISABLED";
+  String get upnpStatus => _upnpStatus;
+
+  // Private network with a pre-shared key (PUBLIC, PRIVATE, INVALID)
+  String _networkMode = "PUBLIC";
+  String get networkMode => _networkMode;
+
+  // Outbound SOCKS5 proxy (OFF, ON, ONLY, INVALID)
+  String _proxyStatus = "OFF";
+  String get proxyStatus => _proxyStatus;
+  bool _networkIdInvalid = false;
+  bool get networkIdInvalid => _networkIdInvalid;
 
   // Custom config
   String _customRelayAddress = "";
   List<String> _customBootstrapNodes = [];
   String get customRelayAddress => _customRelayAddress;
   List<String> get customBootstrapNodes => _customBootstrapNodes;
+  bool _enableUpnp = false;
+  bool get enableUpnp => _enableUpnp;
+  String _socks5Proxy = "";
+  String get socks5Proxy => _socks5Proxy;
+  bool _proxyOnly = false;
+  bool get proxyOnly => _proxyOnly;
+  bool _hideLastSeen = false;
+  bool get hideLastSeen => _hideLastSeen;
 
   // Start method
   Future<void> start(String instanceName) async {
@@ -69,6 +166,26 @@ class NodeManager extends ChangeNotifier {
     stream.listen((String message) {
       _processMessage(message);
     });
+
+    // Wi-Fi / mobile data switches while the app is running. Desktops watch their interfaces in the node
+    if (_isMobile) {
+      final connectivity = Connectivity();
+      _networks = (await connectivity.checkConnectivity()).toSet();
+      connectivity.onConnectivityChanged.listen(_onConnectivityChanged);
+    }
+  }
+
+  // Networks the device is on, to tell real switches from repeated reports
+  Set<ConnectivityResult> _networks = {};
+
+  // Losing every network needs nothing, the node is told once a new one comes up
+  void _onConnectivityChanged(List<ConnectivityResult> results) {
+    final networks = results.toSet();
+    if (setEquals(networks, _networks)) return;
+    _networks = networks;
+    if (!networks.contains(ConnectivityResult.none)) {
+      notifyNetworkChanged();
+    }
   }
 
   // Load known peers for history
@@ -135,6 +252,86 @@ class NodeManager extends ChangeNotifier {
     rust.refreshNode();
   }
 
+  // Presence is looked up in the DHT on demand, answered with PEER+ / PEER-
+  void checkPresence(String peerId) {
+    if (!isPeerOnline(peerId)) rust.checkPresence(peerId: peerId);
+  }
+
+  // Persisted by the node, confirmed through BLOCKED:/UNBLOCKED: events
+  void blockPeer(String peerId) {
+    rust.blockPeer(peerId: peerId);
+  }
+
+  void unblockPeer(String peerId) {
+    rust.unblockPeer(peerId: peerId);
+  }
+
+  // Joined rooms are remembered and rejoined on the next start
+  Future<void> joinRoom(String room) async {
+    room = room.trim();
+    if (room.isEmpty || !_rooms.add(room)) return;
+    rust.joinRoom(room: room);
+    final prefs = await SharedPreferences.getInstance();
+    await prefs.setStringList('joined_rooms', _rooms.toList());
+    notifyListeners();
+  }
+
+  Future<void> leaveRoom(String room) async {
+    if (!_rooms.remove(room)) return;
+    _roomMembers.remove(room);
+    rust.leaveRoom(room: room);
+    final prefs = await SharedPreferences.getInstance();
+    await prefs.setStringList('joined_rooms', _rooms.toList());
+    notifyListeners();
+  }
+
+  Future<void> setPresence(String status, String text) async {
+    _status = status;
+    _statusText = text.trim();
+    final prefs = await SharedPreferences.getInstance();
+    await prefs.setString('presence_status', _status);
+    await prefs.setString('presence_text', _statusText);
+    _publishPresence();
+    notifyListeners();
+  }
+
+  void onAppBackground() {
+    if (_inBackground) return;
+    _inBackground = true;
+    _publishPresence();
+    rust.setPowerMode(mode: "BACKGROUND");
+    if (_isMobile) {
+      _suspendTimer = Timer(_suspendAfter, () => rust.setPowerMode(mode: "SUSPENDED"));
+    }
+  }
+
+  void onAppForeground() {
+    _suspendTimer?.cancel();
+    _suspendTimer = null;
+    _inBackground = false;
+    rust.setPowerMode(mode: "FOREGROUND");
+    _publishPresence();
+    // A phone may have switched networks (Wi-Fi / mobile data) while in the background, where
+    // connectivity reports are not always delivered. Desktops notice interface changes on their own
+    if (_isMobile) {
+      notifyNetworkChanged();
+    }
+  }
+
+  bool get _isMobile =>
+      !kIsWeb && (defaultTargetPlatform == TargetPlatform.android || defaultTargetPlatform == TargetPlatform.iOS);
+
+  // Listen again, reconnect to the relay and republish presence after a network switch
+  void notifyNetworkChanged() {
+    rust.notifyNetworkChanged();
+  }
+
+  // Only an ONLINE user goes AWAY in the background, DND and INVISIBLE are kept
+  void _publishPresence() {
+    final effective = _inBackground && _status == "ONLINE" ? "AWAY" : _status;
+    rust.setPresence(status: effective, text: _statusText);
+  }
+
   void sendMsg(String peerId, String msg) {
     rust.sendMessage(recipient: peerId, msg: msg);
     _storeMessage(peerId, msg, isMe: true);
@@ -157,14 +354,29 @@ class NodeManager extends ChangeNotifier {
 
   void _processMessage(String rawMsg) {
     // Expected format:
-    // PEER+:12D3...
-    // PEER-:12D3...
+    // PEER+:12D3...:MDNS (discovery source)
+    // PEER-:12D3...:1700000000:TIMEOUT (last seen, empty if not shared, and reason)
     // MSG:12D3...:Text
     // MSG_SENT:12D3...:Text (ACK)
+    // CONNECTIVITY:CONNECTED
+    // NAT:PUBLIC
+    // UPNP:MAPPED
+    // PNET:PRIVATE
+    // PROXY:ONLY
+    // NETWORK:INVALID
+    // BLOCKED:12D3...
+    // UNBLOCKED:12D3...
+    // ROOM+:room name:12D3...
+    // PING:12D3...:42:0:1 (rtt ms, failures in a row, failures in total)
+    // STATUS:12D3...:AWAY:status line
+    // PATH:12D3...:DIRECT (empty once disconnected)
 
     if (rawMsg.startsWith("PEER+:")) {
-      final peerId = rawMsg.substring(6);
+      final parts = rawMsg.split(":");
+      final peerId = parts[1];
       _onlinePeers.add(peerId);
+      if (parts.length > 2) _discoverySource[peerId] = parts[2];
+      _departureReason.remove(peerId);
       if (!_peers.contains(peerId)) {
         _peers.add(peerId);
         _loadChatForPeer(peerId);
@@ -172,8 +384,104 @@ class NodeManager extends ChangeNotifier {
       notifyListeners();
     }
     else if (rawMsg.startsWith("PEER-:")) {
-      final peerId = rawMsg.substring(6);
+      final parts = rawMsg.split(":");
+      final peerId = parts[1];
+      final lastSeen = parts.length > 2 ? int.tryParse(parts[2]) : null;
+      if (lastSeen != null) {
+        _lastSeen[peerId] = lastSeen;
+      } else {
+        _lastSeen.remove(peerId);
+      }
+      _discoverySource.remove(peerId);
+      if (parts.length > 3) _departureReason[peerId] = parts[3];
       _onlinePeers.remove(peerId);
+      _peerRtt.remove(peerId);
+      _pingFailures.remove(peerId);
+      _connectionPath.remove(peerId);
+      _peerStatus.remove(peerId);
+      _peerStatusText.remove(peerId);
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("PING:")) {
+      final parts = rawMsg.split(":");
+      if (parts.length == 5) {
+        final rtt = int.tryParse(parts[2]);
+        if (rtt != null) _peerRtt[parts[1]] = rtt;
+        _pingFailures[parts[1]] = [int.tryParse(parts[3]) ?? 0, int.tryParse(parts[4]) ?? 0];
+        notifyListeners();
+      }
+    }
+    else if (rawMsg.startsWith("PATH:")) {
+      final parts = rawMsg.split(":");
+      if (parts.length == 3) {
+        if (parts[2].isEmpty) {
+          _connectionPath.remove(parts[1]);
+        } else {
+          _connectionPath[parts[1]] = parts[2];
+        }
+        notifyListeners();
+      }
+    }
+    else if (rawMsg.startsWith("STATUS:")) {
+      final parts = rawMsg.split(":");
+      if (parts.length >= 3) {
+        _peerStatus[parts[1]] = parts[2];
+        _peerStatusText[parts[1]] = parts.sublist(3).join(":");
+        notifyListeners();
+      }
+    }
+    else if (rawMsg.startsWith("CONNECTIVITY:")) {
+      _connectivity = rawMsg.substring(13);
+      // First event after the node accepts commands
+      if (!_roomsRestored) {
+        _roomsRestored = true;
+        for (final room in _rooms) {
+          rust.joinRoom(room: room);
+        }
+        if (_status != "ONLINE" || _statusText.isNotEmpty || _inBackground) {
+          _publishPresence();
+        }
+      }
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("NAT:")) {
+      _natStatus = rawMsg.substring(4);
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("UPNP:")) {
+      _upnpStatus = rawMsg.substring(5);
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("PNET:")) {
+      _networkMode = rawMsg.substring(5);
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("BLOCKED:")) {
+      final peerId = rawMsg.substring(8);
+      _blockedPeers.add(peerId);
+      _onlinePeers.remove(peerId);
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("UNBLOCKED:")) {
+      _blockedPeers.remove(rawMsg.substring(10));
+      notifyListeners();
+    }
+    else if (rawMsg.startsWith("ROOM+:")) {
+      // Room names may contain ':', the peer id never does
+      final rest = rawMsg.substring(6);
+      final split = rest.lastIndexOf(":");
+      final room = rest.substring(0, split);
+      if (_rooms.contains(room)) {
+        _roomMembers.putIfAbsent(room, () => {}).add(rest.substring(split + 1));
+        notifyListeners();
+      }
+    }
+    else if (rawMsg.startsWith("PROXY:")) {
+      _proxyStatus = rawMsg.substring(6);
+      notifyListeners();
+    }
+    else if (rawMsg == "NETWORK:INVALID") {
+      _networkIdInvalid = true;
       notifyListeners();
     }
     else if (rawMsg.startsWith("MSG:")) {
@@ -187,6 +495,9 @@ class NodeManager extends ChangeNotifier {
 
         if (activeChatPeerId != peerId) {
           _unreadCounts[peerId] = (_unreadCounts[peerId] ?? 0) + 1;
+          if (_status != "DND") {
+            _alerts.add(Message(peerId, content, false, DateTime.now().millisecondsSinceEpoch));
+          }
         }
         notifyListeners();
       }
@@ -220,8 +531,16 @@ class NodeManager extends ChangeNotifier {
 
     _customRelayAddress = prefs.getString('custom_relay_base') ?? "";
     _customBootstrapNodes = prefs.getStringList('custom_bootstraps') ?? [];
+    _enableUpnp = prefs.getBool('enable_upnp') ?? false;
+    _socks5Proxy = prefs.getString('socks5_proxy') ?? "";
+    _proxyOnly = prefs.getBool('proxy_only') ?? false;
+    _hideLastSeen = prefs.getBool('hide_last_seen') ?? false;
+    _rooms.addAll(prefs.getStringList('joined_rooms') ?? []);
+    _status = prefs.getString('presence_status') ?? "ONLINE";
+    _statusText = prefs.getString('presence_text') ?? "";
 
     if (_customRelayAddress.isNotEmpty || _customBootstrapNodes.isNotEmpty) {
+      final deployment = await _bundledDeploymentConfig();
       List<String> finalBootstraps = _customBootstrapNodes;
       if (finalBootstraps.isEmpty && _customRelayAddress.isNotEmpty) {
         finalBootstraps = [_customRelayAddress];
@@ -234,6 +553,16 @@ class NodeManager extends ChangeNotifier {
             : "",
         bootstrapNodes: finalBootstraps,
         listenPort: 0,
+        enableUpnp: _enableUpnp,
+        preSharedKey: deployment['pre_shared_key'] ?? "",
+        networkId: deployment['network_id'] ?? "",
+        socks5Proxy: _socks5Proxy,
+        proxyOnly: _proxyOnly,
+        maxConnections: deployment['max_connections'] ?? 0,
+        maxConnectionsPerPeer: deployment['max_connections_per_peer'] ?? 0,
+        persistDht: deployment['persist_dht'] ?? false,
+        hideLastSeen: _hideLastSeen,
+        dnsFallback: deployment['dns_fallback'] ?? "",
       );
     }
 
@@ -245,6 +574,16 @@ class NodeManager extends ChangeNotifier {
         relayAddress: data['relay_address'] ?? "",
         bootstrapNodes: List<String>.from(data['bootstrap_nodes'] ?? []),
         listenPort: data['listen_port'] ?? 0,
+        enableUpnp: data['enable_upnp'] ?? _enableUpnp,
+        preSharedKey: data['pre_shared_key'] ?? "",
+        networkId: data['network_id'] ?? "",
+        socks5Proxy: data['socks5_proxy'] ?? _socks5Proxy,
+        proxyOnly: data['proxy_only'] ?? _proxyOnly,
+        maxConnections: data['max_connections'] ?? 0,
+        maxConnectionsPerPeer: data['max_connections_per_peer'] ?? 0,
+        persistDht: data['persist_dht'] ?? false,
+        hideLastSeen: data['hide_last_seen'] ?? _hideLastSeen,
+        dnsFallback: data['dns_fallback'] ?? "",
       );
     } catch (e) {
       print("Error loading config asset: $e");
@@ -253,19 +592,50 @@ class NodeManager extends ChangeNotifier {
         relayAddress: "",
         bootstrapNodes: [],
         listenPort: 0,
+        enableUpnp: _enableUpnp,
+        preSharedKey: "",
+        networkId: "",
+        socks5Proxy: _socks5Proxy,
+        proxyOnly: _proxyOnly,
+        maxConnections: 0,
+        maxConnectionsPerPeer: 0,
+        persistDht: false,
+        hideLastSeen: _hideLastSeen,
+        dnsFallback: "",
       );
     }
   }
 
+  // Network id, private network key, connection limits and the DNS fallback are part of the deployment, custom relay settings keep them
+  Future<Map<String, dynamic>> _bundledDeploymentConfig() async {
+    try {
+      return json.decode(await rootBundle.loadString('assets/config.json'));
+    } catch (e) {
+      return {};
+    }
+  }
+
   Future<void> saveNewConfig({
     required String relayBaseAddress,
-    required List<String> bootstrapNodes
+    required List<String> bootstrapNodes,
+    required bool enableUpnp,
+    required String socks5Proxy,
+    required bool proxyOnly,
+    required bool hideLastSeen,
   }) async {
     final prefs = await SharedPreferences.getInstance();
     await prefs.setString('custom_relay_base', relayBaseAddress);
     await prefs.setStringList('custom_bootstraps', bootstrapNodes);
+    await prefs.setBool('enable_upnp', enableUpnp);
+    await prefs.setString('socks5_proxy', socks5Proxy);
+    await prefs.setBool('proxy_only', proxyOnly);
+    await prefs.setBool('hide_last_seen', hideLastSeen);
     _customRelayAddress = relayBaseAddress;
     _customBootstrapNodes = bootstrapNodes;
+    _enableUpnp = enableUpnp;
+    _socks5Proxy = socks5Proxy;
+    _proxyOnly = proxyOnly;
+    _hideLastSeen = hideLastSeen;
     notifyListeners();
   }
 
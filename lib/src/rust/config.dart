@@ -11,12 +11,32 @@ class AppConfig {
   final String relayAddress;
   final List<String> bootstrapNodes;
   final int listenPort;
+  final bool enableUpnp;
+  final String preSharedKey;
+  final String networkId;
+  final String socks5Proxy;
+  final bool proxyOnly;
+  final int maxConnections;
+  final int maxConnectionsPerPeer;
+  final bool persistDht;
+  final bool hideLastSeen;
+  final String dnsFallback;
 
   const AppConfig({
     required this.isBootstrapNode,
     required this.relayAddress,
     required this.bootstrapNodes,
     required this.listenPort,
+    required this.enableUpnp,
+    required this.preSharedKey,
+    required this.networkId,
+    required this.socks5Proxy,
+    required this.proxyOnly,
+    required this.maxConnections,
+    required this.maxConnectionsPerPeer,
+    required this.persistDht,
+    required this.hideLastSeen,
+    required this.dnsFallback,
   });
 
   @override
@@ -24,7 +44,17 @@ class AppConfig {
       isBootstrapNode.hashCode ^
       relayAddress.hashCode ^
       bootstrapNodes.hashCode ^
-      listenPort.hashCode;
+      listenPort.hashCode ^
+      enableUpnp.hashCode ^
+      preSharedKey.hashCode ^
+      networkId.hashCode ^
+      socks5Proxy.hashCode ^
+      proxyOnly.hashCode ^
+      maxConnections.hashCode ^
+      maxConnectionsPerPeer.hashCode ^
+      persistDht.hashCode ^
+      hideLastSeen.hashCode ^
+      dnsFallback.hashCode;
 
   @override
   bool operator ==(Object other) =>
@@ -34,5 +64,15 @@ class AppConfig {
           isBootstrapNode == other.isBootstrapNode &&
           relayAddress == other.relayAddress &&
           bootstrapNodes == other.bootstrapNodes &&
-          listenPort == other.listenPort;
+          listenPort == other.listenPort &&
+          enableUpnp == other.enableUpnp &&
+          preSharedKey == other.preSharedKey &&
+          networkId == other.networkId &&
+          socks5Proxy == other.socks5Proxy &&
+          proxyOnly == other.proxyOnly &&
+          maxConnections == other.maxConnections &&
+          maxConnectionsPerPeer == other.maxConnectionsPerPeer &&
+          persistDht == other.persistDht &&
+          hideLastSeen == other.hideLastSeen &&
+          dnsFallback == other.dnsFallback;
 }
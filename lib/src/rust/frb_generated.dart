@@ -65,7 +65,7 @@ class RustLib extends BaseEntrypoint<RustLibApi, RustLibApiImpl, RustLibWire> {
   String get codegenVersion => '2.11.1';
 
   @override
-  int get rustContentHash => -2146739541;
+  int get rustContentHash => 924283417;
 
   static const kDefaultExternalLibraryLoaderConfig =
       ExternalLibraryLoaderConfig(
@@ -76,15 +76,37 @@ class RustLib extends BaseEntrypoint<RustLibApi, RustLibApiImpl, RustLibWire> {
 }
 
 abstract class RustLibApi extends BaseApi {
+  void crateNodeBlockPeer({required String peerId});
+
+  void crateNodeCheckPresence({required String peerId});
+
+  String crateNodeGetConnectionPath({required String peerId});
+
+  String crateNodeGetHolePunchStats();
+
+  BigInt? crateNodeGetLastSeen({required String peerId});
+
+  void crateNodeJoinRoom({required String room});
+
+  void crateNodeLeaveRoom({required String room});
+
+  void crateNodeNotifyNetworkChanged();
+
   void crateNodeRefreshNode();
 
   void crateNodeSendMessage({required String recipient, required String msg});
 
+  void crateNodeSetPowerMode({required String mode});
+
+  void crateNodeSetPresence({required String status, required String text});
+
   Stream<String> crateNodeStartP2PNode({
     required String storagePath,
     required String instanceName,
     required AppConfig config,
   });
+
+  void crateNodeUnblockPeer({required String peerId});
 }
 
 class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
@@ -96,17 +118,201 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
   });
 
   @override
-  void crateNodeRefreshNode() {
+  void crateNodeBlockPeer({required String peerId}) {
     return handler.executeSync(
       SyncTask(
         callFfi: () {
           final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(peerId, serializer);
           return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 1)!;
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_unit,
           decodeErrorData: null,
         ),
+        constMeta: kCrateNodeBlockPeerConstMeta,
+        argValues: [peerId],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeBlockPeerConstMeta =>
+      const TaskConstMeta(debugName: "block_peer", argNames: ["peerId"]);
+
+  @override
+  void crateNodeCheckPresence({required String peerId}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(peerId, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 2)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeCheckPresenceConstMeta,
+        argValues: [peerId],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeCheckPresenceConstMeta =>
+      const TaskConstMeta(debugName: "check_presence", argNames: ["peerId"]);
+
+  @override
+  String crateNodeGetConnectionPath({required String peerId}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(peerId, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 3)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_String,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeGetConnectionPathConstMeta,
+        argValues: [peerId],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeGetConnectionPathConstMeta => const TaskConstMeta(
+    debugName: "get_connection_path",
+    argNames: ["peerId"],
+  );
+
+  @override
+  String crateNodeGetHolePunchStats() {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 4)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_String,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeGetHolePunchStatsConstMeta,
+        argValues: [],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeGetHolePunchStatsConstMeta =>
+      const TaskConstMeta(debugName: "get_hole_punch_stats", argNames: []);
+
+  @override
+  BigInt? crateNodeGetLastSeen({required String peerId}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(peerId, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 5)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_opt_box_autoadd_u_64,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeGetLastSeenConstMeta,
+        argValues: [peerId],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeGetLastSeenConstMeta =>
+      const TaskConstMeta(debugName: "get_last_seen", argNames: ["peerId"]);
+
+  @override
+  void crateNodeJoinRoom({required String room}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(room, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 6)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeJoinRoomConstMeta,
+        argValues: [room],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeJoinRoomConstMeta =>
+      const TaskConstMeta(debugName: "join_room", argNames: ["room"]);
+
+  @override
+  void crateNodeLeaveRoom({required String room}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(room, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 7)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeLeaveRoomConstMeta,
+        argValues: [room],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeLeaveRoomConstMeta =>
+      const TaskConstMeta(debugName: "leave_room", argNames: ["room"]);
+
+  @override
+  void crateNodeNotifyNetworkChanged() {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 8)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeNotifyNetworkChangedConstMeta,
+        argValues: [],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeNotifyNetworkChangedConstMeta =>
+      const TaskConstMeta(debugName: "notify_network_changed", argNames: []);
+
+  @override
+  void crateNodeRefreshNode() {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 9)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
         constMeta: kCrateNodeRefreshNodeConstMeta,
         argValues: [],
         apiImpl: this,
@@ -125,7 +331,7 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
           final serializer = SseSerializer(generalizedFrbRustBinding);
           sse_encode_String(recipient, serializer);
           sse_encode_String(msg, serializer);
-          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 2)!;
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 10)!;
         },
         codec: SseCodec(
           decodeSuccessData: sse_decode_unit,
@@ -143,6 +349,55 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     argNames: ["recipient", "msg"],
   );
 
+  @override
+  void crateNodeSetPowerMode({required String mode}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(mode, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 11)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeSetPowerModeConstMeta,
+        argValues: [mode],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeSetPowerModeConstMeta =>
+      const TaskConstMeta(debugName: "set_power_mode", argNames: ["mode"]);
+
+  @override
+  void crateNodeSetPresence({required String status, required String text}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(status, serializer);
+          sse_encode_String(text, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 12)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeSetPresenceConstMeta,
+        argValues: [status, text],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeSetPresenceConstMeta => const TaskConstMeta(
+    debugName: "set_presence",
+    argNames: ["status", "text"],
+  );
+
   @override
   Stream<String> crateNodeStartP2PNode({
     required String storagePath,
@@ -162,7 +417,7 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
             pdeCallFfi(
               generalizedFrbRustBinding,
               serializer,
-              funcId: 3,
+              funcId: 13,
               port: port_,
             );
           },
@@ -184,6 +439,29 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     argNames: ["sink", "storagePath", "instanceName", "config"],
   );
 
+  @override
+  void crateNodeUnblockPeer({required String peerId}) {
+    return handler.executeSync(
+      SyncTask(
+        callFfi: () {
+          final serializer = SseSerializer(generalizedFrbRustBinding);
+          sse_encode_String(peerId, serializer);
+          return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 14)!;
+        },
+        codec: SseCodec(
+          decodeSuccessData: sse_decode_unit,
+          decodeErrorData: null,
+        ),
+        constMeta: kCrateNodeUnblockPeerConstMeta,
+        argValues: [peerId],
+        apiImpl: this,
+      ),
+    );
+  }
+
+  TaskConstMeta get kCrateNodeUnblockPeerConstMeta =>
+      const TaskConstMeta(debugName: "unblock_peer", argNames: ["peerId"]);
+
   @protected
   AnyhowException dco_decode_AnyhowException(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
@@ -206,13 +484,23 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
   AppConfig dco_decode_app_config(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
     final arr = raw as List<dynamic>;
-    if (arr.length != 4)
-      throw Exception('unexpected arr length: expect 4 but see ${arr.length}');
+    if (arr.length != 14)
+      throw Exception('unexpected arr length: expect 13 but see ${arr.length}');
     return AppConfig(
       isBootstrapNode: dco_decode_bool(arr[0]),
       relayAddress: dco_decode_String(arr[1]),
       bootstrapNodes: dco_decode_list_String(arr[2]),
       listenPort: dco_decode_u_16(arr[3]),
+      enableUpnp: dco_decode_bool(arr[4]),
+      preSharedKey: dco_decode_String(arr[5]),
+      networkId: dco_decode_String(arr[6]),
+      socks5Proxy: dco_decode_String(arr[7]),
+      proxyOnly: dco_decode_bool(arr[8]),
+      maxConnections: dco_decode_u_16(arr[9]),
+      maxConnectionsPerPeer: dco_decode_u_16(arr[10]),
+      persistDht: dco_decode_bool(arr[11]),
+      hideLastSeen: dco_decode_bool(arr[12]),
+      dnsFallback: dco_decode_String(arr[13]),
     );
   }
 
@@ -228,6 +516,12 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     return dco_decode_app_config(raw);
   }
 
+  @protected
+  BigInt dco_decode_box_autoadd_u_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_u_64(raw);
+  }
+
   @protected
   List<String> dco_decode_list_String(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
@@ -240,12 +534,24 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     return raw as Uint8List;
   }
 
+  @protected
+  BigInt? dco_decode_opt_box_autoadd_u_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw == null ? null : dco_decode_box_autoadd_u_64(raw);
+  }
+
   @protected
   int dco_decode_u_16(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
     return raw as int;
   }
 
+  @protected
+  BigInt dco_decode_u_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dcoDecodeU64(raw);
+  }
+
   @protected
   int dco_decode_u_8(dynamic raw) {
     // Codec=Dco (DartCObject based), see doc to use other codecs
@@ -287,11 +593,31 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     var var_relayAddress = sse_decode_String(deserializer);
     var var_bootstrapNodes = sse_decode_list_String(deserializer);
     var var_listenPort = sse_decode_u_16(deserializer);
+    var var_enableUpnp = sse_decode_bool(deserializer);
+    var var_preSharedKey = sse_decode_String(deserializer);
+    var var_networkId = sse_decode_String(deserializer);
+    var var_socks5Proxy = sse_decode_String(deserializer);
+    var var_proxyOnly = sse_decode_bool(deserializer);
+    var var_maxConnections = sse_decode_u_16(deserializer);
+    var var_maxConnectionsPerPeer = sse_decode_u_16(deserializer);
+    var var_persistDht = sse_decode_bool(deserializer);
+    var var_hideLastSeen = sse_decode_bool(deserializer);
+    var var_dnsFallback = sse_decode_String(deserializer);
     return AppConfig(
       isBootstrapNode: var_isBootstrapNode,
       relayAddress: var_relayAddress,
       bootstrapNodes: var_bootstrapNodes,
       listenPort: var_listenPort,
+      enableUpnp: var_enableUpnp,
+      preSharedKey: var_preSharedKey,
+      networkId: var_networkId,
+      socks5Proxy: var_socks5Proxy,
+      proxyOnly: var_proxyOnly,
+      maxConnections: var_maxConnections,
+      maxConnectionsPerPeer: var_maxConnectionsPerPeer,
+      persistDht: var_persistDht,
+      hideLastSeen: var_hideLastSeen,
+      dnsFallback: var_dnsFallback,
     );
   }
 
@@ -307,6 +633,12 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     return (sse_decode_app_config(deserializer));
   }
 
+  @protected
+  BigInt sse_decode_box_autoadd_u_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_u_64(deserializer));
+  }
+
   @protected
   List<String> sse_decode_list_String(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
@@ -326,12 +658,29 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     return deserializer.buffer.getUint8List(len_);
   }
 
+  @protected
+  BigInt? sse_decode_opt_box_autoadd_u_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    if (sse_decode_bool(deserializer)) {
+      return (sse_decode_box_autoadd_u_64(deserializer));
+    } else {
+      return null;
+    }
+  }
+
   @protected
   int sse_decode_u_16(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
     return deserializer.buffer.getUint16();
   }
 
+  @protected
+  BigInt sse_decode_u_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return deserializer.buffer.getBigUint64();
+  }
+
   @protected
   int sse_decode_u_8(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
@@ -388,6 +737,16 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     sse_encode_String(self.relayAddress, serializer);
     sse_encode_list_String(self.bootstrapNodes, serializer);
     sse_encode_u_16(self.listenPort, serializer);
+    sse_encode_bool(self.enableUpnp, serializer);
+    sse_encode_String(self.preSharedKey, serializer);
+    sse_encode_String(self.networkId, serializer);
+    sse_encode_String(self.socks5Proxy, serializer);
+    sse_encode_bool(self.proxyOnly, serializer);
+    sse_encode_u_16(self.maxConnections, serializer);
+    sse_encode_u_16(self.maxConnectionsPerPeer, serializer);
+    sse_encode_bool(self.persistDht, serializer);
+    sse_encode_bool(self.hideLastSeen, serializer);
+    sse_encode_String(self.dnsFallback, serializer);
   }
 
   @protected
@@ -405,6 +764,12 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     sse_encode_app_config(self, serializer);
   }
 
+  @protected
+  void sse_encode_box_autoadd_u_64(BigInt self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_u_64(self, serializer);
+  }
+
   @protected
   void sse_encode_list_String(List<String> self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
@@ -424,6 +789,16 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     serializer.buffer.putUint8List(self);
   }
 
+  @protected
+  void sse_encode_opt_box_autoadd_u_64(BigInt? self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    sse_encode_bool(self != null, serializer);
+    if (self != null) {
+      sse_encode_box_autoadd_u_64(self, serializer);
+    }
+  }
+
   @protected
   void sse_encode_u_16(int self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
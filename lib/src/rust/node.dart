@@ -21,3 +21,35 @@ Stream<String> startP2PNode({
 );
 
 void refreshNode() => RustLib.instance.api.crateNodeRefreshNode();
+
+void blockPeer({required String peerId}) =>
+    RustLib.instance.api.crateNodeBlockPeer(peerId: peerId);
+
+void unblockPeer({required String peerId}) =>
+    RustLib.instance.api.crateNodeUnblockPeer(peerId: peerId);
+
+void joinRoom({required String room}) =>
+    RustLib.instance.api.crateNodeJoinRoom(room: room);
+
+void leaveRoom({required String room}) =>
+    RustLib.instance.api.crateNodeLeaveRoom(room: room);
+
+void checkPresence({required String peerId}) =>
+    RustLib.instance.api.crateNodeCheckPresence(peerId: peerId);
+
+void setPresence({required String status, required String text}) =>
+    RustLib.instance.api.crateNodeSetPresence(status: status, text: text);
+
+BigInt? getLastSeen({required String peerId}) =>
+    RustLib.instance.api.crateNodeGetLastSeen(peerId: peerId);
+
+String getConnectionPath({required String peerId}) =>
+    RustLib.instance.api.crateNodeGetConnectionPath(peerId: peerId);
+
+String getHolePunchStats() => RustLib.instance.api.crateNodeGetHolePunchStats();
+
+void notifyNetworkChanged() =>
+    RustLib.instance.api.crateNodeNotifyNetworkChanged();
+
+void setPowerMode({required String mode}) =>
+    RustLib.instance.api.crateNodeSetPowerMode(mode: mode);
@@ -1,3 +1,4 @@
+import 'dart:async';
 import 'package:flutter/material.dart';
 import '../../main.dart';
 import 'settings_screen.dart';
@@ -13,15 +14,27 @@ class HomeScreen extends StatefulWidget {
 
 class _HomeScreenState extends State<HomeScreen> with WidgetsBindingObserver {
   String? _selectedPeer;
+  StreamSubscription? _alertSubscription;
 
   @override
   void initState() {
     super.initState();
     WidgetsBinding.instance.addObserver(this);
+    // In-app notification for messages outside the open chat (suppressed in Do Not Disturb)
+    _alertSubscription = nodeManager.alerts.listen((message) {
+      if (!mounted) return;
+      ScaffoldMessenger.of(context).showSnackBar(
+        SnackBar(
+          content: Text("Peer ${message.peerId.substring(0, 5)}...: ${message.content}"),
+          duration: const Duration(seconds: 2),
+        ),
+      );
+    });
   }
 
   @override
   void dispose() {
+    _alertSubscription?.cancel();
     WidgetsBinding.instance.removeObserver(this);
     super.dispose();
   }
@@ -29,7 +42,10 @@ class _HomeScreenState extends State<HomeScreen> with WidgetsBindingObserver {
   @override
   void didChangeAppLifecycleState(AppLifecycleState state) {
     if (state == AppLifecycleState.resumed) {
+      nodeManager.onAppForeground();
       nodeManager.refreshNode();
+    } else if (state == AppLifecycleState.paused || state == AppLifecycleState.hidden) {
+      nodeManager.onAppBackground();
     }
   }
 
@@ -55,7 +71,46 @@ class _HomeScreenState extends State<HomeScreen> with WidgetsBindingObserver {
                   )
                 : null,
             actions: [
+              if (showChat)
+                IconButton(
+                  icon: Icon(nodeManager.isPeerBlocked(_selectedPeer!) ? Icons.lock_open : Icons.block),
+                  tooltip: nodeManager.isPeerBlocked(_selectedPeer!) ? "Unblock Peer" : "Block Peer",
+                  onPressed: () {
+                    final peerId = _selectedPeer!;
+                    if (nodeManager.isPeerBlocked(peerId)) {
+                      nodeManager.unblockPeer(peerId);
+                    } else {
+                      nodeManager.blockPeer(peerId);
+                    }
+                  },
+                ),
               if (!showChat) ...[
+                // --- CONNECTIVITY INDICATOR ---
+                Padding(
+                  padding: const EdgeInsets.symmetric(horizontal: 8),
+                  child: Tooltip(
+                    message: nodeManager.networkMode == "INVALID"
+                        ? "Invalid pre-shared key, node not started"
+                        : nodeManager.proxyStatus == "INVALID"
+                            ? "Invalid SOCKS5 proxy, node not started"
+                            : nodeManager.networkIdInvalid
+                                ? "Network id too long, node not started"
+                                : "Network: ${nodeManager.connectivity} (NAT: ${nodeManager.natStatus})"
+                                    "${nodeManager.networkMode == "PRIVATE" ? " - private network" : ""}"
+                                    "${nodeManager.proxyStatus == "ONLY" ? " - proxy only" : nodeManager.proxyStatus == "ON" ? " - via proxy" : ""}",
+                    child: Icon(
+                      nodeManager.networkMode == "INVALID" || nodeManager.proxyStatus == "INVALID" ||
+                              nodeManager.networkIdInvalid
+                          ? Icons.error_outline
+                          : nodeManager.connectivity == "CONNECTED"
+                              ? Icons.cloud_done
+                              : nodeManager.connectivity == "RECONNECTING"
+                                  ? Icons.cloud_off
+                                  : Icons.cloud_queue,
+                    ),
+                  ),
+                ),
+
                 // --- REFRESH BUTTON ---
                 IconButton(
                   icon: const Icon(Icons.refresh),
@@ -99,6 +154,7 @@ class _HomeScreenState extends State<HomeScreen> with WidgetsBindingObserver {
                       nodeManager.activeChatPeerId = peerId;
                       nodeManager.markAsRead(peerId);
                     });
+                    nodeManager.checkPresence(peerId);
                   },
                 ),
         );
@@ -11,19 +11,33 @@ class SettingsScreen extends StatefulWidget {
 class _SettingsScreenState extends State<SettingsScreen> {
   final TextEditingController _relayController = TextEditingController();
   final TextEditingController _bootstrapController = TextEditingController();
+  final TextEditingController _proxyController = TextEditingController();
+  final TextEditingController _roomController = TextEditingController();
+  final TextEditingController _statusTextController = TextEditingController();
   final nodeManager = NodeManager();
+  bool _enableUpnp = false;
+  bool _proxyOnly = false;
+  bool _hideLastSeen = false;
 
   @override
   void initState() {
     super.initState();
     _relayController.text = nodeManager.customRelayAddress;
     _bootstrapController.text = nodeManager.customBootstrapNodes.join('\n');
+    _enableUpnp = nodeManager.enableUpnp;
+    _proxyController.text = nodeManager.socks5Proxy;
+    _proxyOnly = nodeManager.proxyOnly;
+    _hideLastSeen = nodeManager.hideLastSeen;
+    _statusTextController.text = nodeManager.statusText;
   }
 
   @override
   void dispose() {
     _relayController.dispose();
     _bootstrapController.dispose();
+    _proxyController.dispose();
+    _roomController.dispose();
+    _statusTextController.dispose();
     super.dispose();
   }
 
@@ -36,12 +50,54 @@ class _SettingsScreenState extends State<SettingsScreen> {
         child: Column(
           crossAxisAlignment: CrossAxisAlignment.start,
           children: [
+            // Status applies right away, no restart needed
+            const Text("Status", style: TextStyle(fontWeight: FontWeight.bold)),
+            const SizedBox(height: 8),
+            AnimatedBuilder(
+              animation: nodeManager,
+              builder: (context, _) {
+                return DropdownButtonFormField<String>(
+                  value: nodeManager.status,
+                  decoration: const InputDecoration(
+                    border: OutlineInputBorder(),
+                    prefixIcon: Icon(Icons.circle_notifications),
+                  ),
+                  items: const [
+                    DropdownMenuItem(value: "ONLINE", child: Text("Online")),
+                    DropdownMenuItem(value: "AWAY", child: Text("Away")),
+                    DropdownMenuItem(value: "DND", child: Text("Do Not Disturb")),
+                    DropdownMenuItem(value: "INVISIBLE", child: Text("Invisible")),
+                  ],
+                  onChanged: (value) {
+                    if (value != null) nodeManager.setPresence(value, _statusTextController.text);
+                  },
+                );
+              },
+            ),
+            const SizedBox(height: 8),
+            TextField(
+              controller: _statusTextController,
+              maxLength: 140,
+              decoration: InputDecoration(
+                hintText: "What's on your mind?",
+                helperText: "Away is set automatically while the app is in the background",
+                border: const OutlineInputBorder(),
+                prefixIcon: const Icon(Icons.chat_bubble_outline),
+                suffixIcon: IconButton(
+                  icon: const Icon(Icons.check),
+                  onPressed: () => nodeManager.setPresence(nodeManager.status, _statusTextController.text),
+                ),
+              ),
+              onSubmitted: (text) => nodeManager.setPresence(nodeManager.status, text),
+            ),
+            const SizedBox(height: 24),
             const Text("Relay Node Address", style: TextStyle(fontWeight: FontWeight.bold)),
             const SizedBox(height: 8),
             TextField(
               controller: _relayController,
               decoration: const InputDecoration(
                 hintText: "/ip4/X.X.X.X/tcp/4001/p2p/ID...",
+                helperText: "Leave empty to discover relays through the bootstrap nodes",
                 border: OutlineInputBorder(),
                 prefixIcon: Icon(Icons.router),
               ),
@@ -58,6 +114,45 @@ class _SettingsScreenState extends State<SettingsScreen> {
                 prefixIcon: Icon(Icons.group_work),
               ),
             ),
+            const SizedBox(height: 16),
+            SwitchListTile(
+              title: const Text("UPnP Port Mapping"),
+              subtitle: Text(
+                "Open a port on the home router, the relay is only used if it fails (${nodeManager.upnpStatus})",
+              ),
+              secondary: const Icon(Icons.settings_ethernet),
+              value: _enableUpnp,
+              onChanged: _proxyOnly ? null : (value) => setState(() => _enableUpnp = value),
+            ),
+            const SizedBox(height: 24),
+            const Text("SOCKS5 Proxy", style: TextStyle(fontWeight: FontWeight.bold)),
+            const SizedBox(height: 8),
+            TextField(
+              controller: _proxyController,
+              decoration: InputDecoration(
+                hintText: "127.0.0.1:9050",
+                helperText: "Outbound proxy such as a local Tor client, optionally user:password@host:port"
+                    "${nodeManager.proxyStatus == "INVALID" ? " (invalid, node not started)" : ""}",
+                border: const OutlineInputBorder(),
+                prefixIcon: const Icon(Icons.vpn_lock),
+              ),
+            ),
+            SwitchListTile(
+              title: const Text("Proxy Only"),
+              subtitle: const Text(
+                "Hide your IP: connect only through the proxy, no local discovery, direct connections or UPnP",
+              ),
+              secondary: const Icon(Icons.privacy_tip),
+              value: _proxyOnly,
+              onChanged: (value) => setState(() => _proxyOnly = value),
+            ),
+            SwitchListTile(
+              title: const Text("Hide Last Seen"),
+              subtitle: const Text("Ask other peers not to record when you were last online"),
+              secondary: const Icon(Icons.visibility_off),
+              value: _hideLastSeen,
+              onChanged: (value) => setState(() => _hideLastSeen = value),
+            ),
             const SizedBox(height: 30),
             Center(
               child: SizedBox(
@@ -75,6 +170,10 @@ class _SettingsScreenState extends State<SettingsScreen> {
                     await nodeManager.saveNewConfig(
                       relayBaseAddress: relay,
                       bootstrapNodes: bootstraps,
+                      enableUpnp: _enableUpnp && !_proxyOnly,
+                      socks5Proxy: _proxyController.text.trim(),
+                      proxyOnly: _proxyOnly,
+                      hideLastSeen: _hideLastSeen,
                     );
 
                     if (context.mounted) {
@@ -88,6 +187,88 @@ class _SettingsScreenState extends State<SettingsScreen> {
                 ),
               ),
             ),
+            // Rooms: peers registered under the same rendezvous namespace find each other through the relay
+            const SizedBox(height: 30),
+            const Text("Rooms", style: TextStyle(fontWeight: FontWeight.bold)),
+            const SizedBox(height: 8),
+            TextField(
+              controller: _roomController,
+              decoration: InputDecoration(
+                hintText: "Room name",
+                border: const OutlineInputBorder(),
+                prefixIcon: const Icon(Icons.meeting_room),
+                suffixIcon: IconButton(
+                  icon: const Icon(Icons.add),
+                  onPressed: () {
+                    nodeManager.joinRoom(_roomController.text);
+                    _roomController.clear();
+                  },
+                ),
+              ),
+            ),
+            AnimatedBuilder(
+              animation: nodeManager,
+              builder: (context, _) {
+                return Column(
+                  children: [
+                    for (final room in nodeManager.rooms)
+                      ListTile(
+                        leading: const Icon(Icons.group),
+                        title: Text(room),
+                        subtitle: Text("${nodeManager.roomMembers(room).length} peers found"),
+                        trailing: TextButton(
+                          onPressed: () => nodeManager.leaveRoom(room),
+                          child: const Text("Leave"),
+                        ),
+                      ),
+                  ],
+                );
+              },
+            ),
+            // Blocked peers, kept in sync with BLOCKED:/UNBLOCKED: events
+            AnimatedBuilder(
+              animation: nodeManager,
+              builder: (context, _) {
+                if (nodeManager.blockedPeers.isEmpty) return const SizedBox.shrink();
+                return Column(
+                  crossAxisAlignment: CrossAxisAlignment.start,
+                  children: [
+                    const SizedBox(height: 30),
+                    const Text("Blocked Peers", style: TextStyle(fontWeight: FontWeight.bold)),
+                    const SizedBox(height: 8),
+                    for (final peerId in nodeManager.blockedPeers)
+                      ListTile(
+                        leading: const Icon(Icons.block),
+                        title: Text(
+                          peerId,
+                          style: const TextStyle(fontSize: 12),
+                          maxLines: 1,
+                          overflow: TextOverflow.ellipsis,
+                        ),
+                        trailing: TextButton(
+                          onPressed: () => nodeManager.unblockPeer(peerId),
+                          child: const Text("Unblock"),
+                        ),
+                      ),
+                  ],
+                );
+              },
+            ),
+            // Hole punching statistics, to tell why chats stay on the relay
+            Builder(
+              builder: (context) {
+                final total = nodeManager.holePunchStats()['total'] as Map<String, dynamic>?;
+                if (total == null || total['attempts'] == 0) return const SizedBox.shrink();
+                return ListTile(
+                  leading: const Icon(Icons.swap_horiz),
+                  title: const Text("Direct Connections"),
+                  subtitle: Text(
+                    "Hole punching: ${total['successes']} of ${total['attempts']} succeeded, "
+                    "avg ${total['avg_ms']} ms",
+                  ),
+                );
+              },
+            ),
             const SizedBox(height: 50),
             // Delete caht history
             const Divider(color: Colors.red),
@@ -43,6 +43,14 @@ class PeerListView extends StatelessWidget {
             final unreadCount = nodeManager.unreadCount(peerId);
             final avatarColor = Colors.primaries[peerId.hashCode % Colors.primaries.length];
             final isOnline = nodeManager.isPeerOnline(peerId);
+            final isBlocked = nodeManager.isPeerBlocked(peerId);
+            final rtt = isOnline ? nodeManager.peerRtt(peerId) : null;
+            final path = isOnline ? nodeManager.connectionPath(peerId) : null;
+            final status = nodeManager.peerStatus(peerId);
+            final statusText = isOnline ? nodeManager.peerStatusText(peerId) : "";
+            final lastSeen = isOnline ? null : nodeManager.lastSeen(peerId);
+            // Discovery source while online, reason of the disconnect once offline
+            final via = isOnline ? nodeManager.discoverySource(peerId) : nodeManager.departureReason(peerId);
 
             return Card(
               elevation: 2,
@@ -64,7 +72,12 @@ class PeerListView extends StatelessWidget {
                         width: 12,
                         height: 12,
                         decoration: BoxDecoration(
-                          color: isOnline ? Colors.green : Colors.grey,
+                          color: switch (status) {
+                            "ONLINE" => Colors.green,
+                            "AWAY" => Colors.amber,
+                            "DND" => Colors.red,
+                            _ => Colors.grey,
+                          },
                           shape: BoxShape.circle,
                           border: Border.all(color: Colors.white, width: 2),
                         ),
@@ -77,7 +90,14 @@ class PeerListView extends StatelessWidget {
                   style: const TextStyle(fontWeight: FontWeight.bold, fontSize: 16),
                 ),
                 subtitle: Text(
-                  peerId,
+                  [
+                    if (statusText.isNotEmpty) statusText,
+                    if (rtt != null) "$rtt ms",
+                    if (path != null) path.toLowerCase().replaceAll("_", " "),
+                    if (lastSeen != null) "last seen ${_ago(lastSeen)}",
+                    if (via != null) via.toLowerCase().replaceAll("_", " "),
+                    peerId,
+                  ].join(" · "),
                   style: TextStyle(fontSize: 10, color: Colors.grey[600]),
                   maxLines: 1,
                   overflow: TextOverflow.ellipsis,
@@ -94,7 +114,7 @@ class PeerListView extends StatelessWidget {
                           style: const TextStyle(color: Colors.white, fontWeight: FontWeight.bold),
                         ),
                       )
-                    : const Icon(Icons.chevron_right, color: Colors.grey),
+                    : Icon(isBlocked ? Icons.block : Icons.chevron_right, color: Colors.grey),
                 onTap: () => onPeerSelected(peerId),
               ),
             );
@@ -103,4 +123,12 @@ class PeerListView extends StatelessWidget {
       },
     );
   }
+
+  static String _ago(DateTime time) {
+    final elapsed = DateTime.now().difference(time);
+    if (elapsed.inMinutes < 1) return "just now";
+    if (elapsed.inHours < 1) return "${elapsed.inMinutes} min ago";
+    if (elapsed.inDays < 1) return "${elapsed.inHours} h ago";
+    return "${elapsed.inDays} d ago";
+  }
 }
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+// Addresses kept per peer, newest first
+pub const MAX_ADDRESSES_PER_PEER: usize = 8;
+// Peers kept in the book, the least recently successful are dropped first
+pub const MAX_PEERS: usize = 512;
+// Peers never reached again within this time are forgotten
+pub const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 3600);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Entry {
+    addresses: Vec<String>,
+    // Unix seconds of the last time an address was learned
+    last_seen: u64,
+    // Unix seconds of the last established connection
+    last_success: Option<u64>,
+}
+
+// Known peers and their addresses, persisted in <storage_path>/address_book_<instance>.json
+// so Kademlia and the first dials do not depend on the relay and bootstrap nodes being up
+pub struct AddressBook {
+    path: PathBuf,
+    peers: HashMap<PeerId, Entry>,
+    dirty: bool,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Stored without the trailing /p2p/<peer>, as Kademlia expects
+fn without_peer_id(addr: &Multiaddr, peer: &PeerId) -> Multiaddr {
+    let mut addr = addr.clone();
+    if addr.iter().last() == Some(Protocol::P2p(*peer)) {
+        addr.pop();
+    }
+    addr
+}
+
+impl AddressBook {
+    pub fn load(storage_path: &str, instance_name: &str) -> Self {
+        let mut path = PathBuf::from(storage_path);
+        path.push(format!("address_book_{}.json", instance_name));
+
+        let stored: HashMap<String, Entry> = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("[ADDRESS BOOK] Ignoring corrupt {:?}: {}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        let peers = stored.into_iter()
+            .filter_map(|(peer, entry)| Some((peer.parse().ok()?, entry)))
+            .collect();
+
+        let mut book = Self { path, peers, dirty: false };
+        book.prune();
+        book
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn add_address(&mut self, peer: PeerId, addr: &Multiaddr) {
+        let addr = without_peer_id(addr, &peer).to_string();
+        let entry = self.peers.entry(peer).or_default();
+        entry.addresses.retain(|a| *a != addr);
+        entry.addresses.insert(0, addr);
+        entry.addresses.truncate(MAX_ADDRESSES_PER_PEER);
+        entry.last_seen = now();
+        self.dirty = true;
+    }
+
+    // Connection established, `addr` is the address we dialed (None for inbound connections)
+    pub fn record_success(&mut self, peer: PeerId, addr: Option<&Multiaddr>) {
+        if let Some(addr) = addr {
+            self.add_address(peer, addr);
+        }
+        if let Some(entry) = self.peers.get_mut(&peer) {
+            entry.last_success = Some(now());
+            self.dirty = true;
+        }
+    }
+
+    pub fn remove(&mut self, peer: &PeerId) {
+        if self.peers.remove(peer).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    // All peers with their addresses, most recently successful first
+    pub fn peers(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        let mut entries: Vec<_> = self.peers.iter().collect();
+        entries.sort_by_key(|(_, e)| std::cmp::Reverse((e.last_success, e.last_seen)));
+        entries.into_iter()
+            .map(|(peer, entry)| {
+                let addrs = entry.addresses.iter().filter_map(|a| a.parse().ok()).collect();
+                (*peer, addrs)
+            })
+            .collect()
+    }
+
+    // Peers we managed to connect to before, worth dialing on startup
+    pub fn dial_candidates(&self, limit: usize) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        self.peers()
+            .into_iter()
+            .filter(|(peer, addrs)| !addrs.is_empty() && self.peers[peer].last_success.is_some())
+            .take(limit)
+            .collect()
+    }
+
+    pub fn save_if_dirty(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.prune();
+        let stored: HashMap<String, &Entry> = self.peers.iter().map(|(p, e)| (p.to_string(), e)).collect();
+        match serde_json::to_string(&stored) {
+            Ok(text) => {
+                if let Err(e) = fs::write(&self.path, text) {
+                    warn!("[ADDRESS BOOK] Failed to save {:?}: {}", self.path, e);
+                    return;
+                }
+                self.dirty = false;
+            },
+            Err(e) => warn!("[ADDRESS BOOK] Failed to serialize: {}", e),
+        }
+    }
+
+    fn prune(&mut self) {
+        let cutoff = now().saturating_sub(MAX_AGE.as_secs());
+        let before = self.peers.len();
+        self.peers.retain(|_, e| e.last_success.unwrap_or(e.last_seen) >= cutoff && !e.addresses.is_empty());
+
+        if self.peers.len() > MAX_PEERS {
+            let mut ranked: Vec<(PeerId, (Option<u64>, u64))> = self.peers.iter()
+                .map(|(p, e)| (*p, (e.last_success, e.last_seen)))
+                .collect();
+            ranked.sort_by_key(|(_, key)| std::cmp::Reverse(*key));
+            for (peer, _) in ranked.into_iter().skip(MAX_PEERS) {
+                self.peers.remove(&peer);
+            }
+        }
+        if self.peers.len() != before {
+            self.dirty = true;
+        }
+    }
+}
@@ -1,17 +1,165 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+use std::task::{Context, Poll};
 use libp2p::{
-    gossipsub, identify, kad::{store::MemoryStore, Behaviour as Kademlia},
+    core::Endpoint,
+    gossipsub, identify, kad::Behaviour as Kademlia,
     mdns::tokio::Behaviour as Mdns, ping::Behaviour as Ping,
-    relay, dcutr, swarm::NetworkBehaviour
+    relay, dcutr, autonat, upnp, allow_block_list, connection_limits, memory_connection_limits, rendezvous,
+    swarm::{
+        behaviour::{toggle::Toggle, ConnectionClosed, ConnectionEstablished},
+        ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
 };
 
+use crate::record_store::DhtStore;
+
 #[derive(NetworkBehaviour)]
 pub struct MyP2PBehaviour {
-    pub kademlia: Kademlia<MemoryStore>,
+    // Block list and limits first, so a refused connection never reaches the other behaviours
+    pub blocked_peers: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+    pub connection_limits: connection_limits::Behaviour,
+    pub memory_limits: Toggle<memory_connection_limits::Behaviour>,
+    pub kademlia: Kademlia<DhtStore>,
     pub ping: Ping,
-    pub mdns: Mdns,
-    pub gossipsub: gossipsub::Behaviour,
+    pub mdns: Toggle<Mdns>,
+    pub gossipsub: Replaceable<gossipsub::Behaviour>,
     pub relay_client: relay::client::Behaviour,
     pub relay_server: relay::Behaviour,
-    pub dcutr_handler: dcutr::Behaviour,
+    pub dcutr_handler: Toggle<dcutr::Behaviour>,
     pub identify: identify::Behaviour,
+    pub autonat: autonat::Behaviour,
+    pub upnp: Toggle<upnp::tokio::Behaviour>,
+    pub rendezvous: rendezvous::client::Behaviour,
+}
+
+// A behaviour that can be swapped for a freshly configured one while the swarm runs.
+// Connections opened before the swap keep the handlers of the old behaviour, their events are
+// dropped and the new one only hears about its own connections, so close them to move them over
+pub struct Replaceable<B> {
+    inner: B,
+    // Handlers created by the current behaviour, not yet reported as established
+    created: HashSet<ConnectionId>,
+    // Established connections the current behaviour knows, per peer
+    known: HashMap<PeerId, HashSet<ConnectionId>>,
+}
+
+impl<B> Replaceable<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner, created: HashSet::new(), known: HashMap::new() }
+    }
+
+    pub fn replace(&mut self, inner: B) {
+        self.inner = inner;
+        self.created.clear();
+        self.known.clear();
+    }
+
+    fn is_known(&self, peer: &PeerId, connection_id: &ConnectionId) -> bool {
+        self.known.get(peer).is_some_and(|c| c.contains(connection_id))
+    }
+}
+
+impl<B> Deref for Replaceable<B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        &self.inner
+    }
+}
+
+impl<B> DerefMut for Replaceable<B> {
+    fn deref_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+}
+
+impl<B: NetworkBehaviour> NetworkBehaviour for Replaceable<B> {
+    type ConnectionHandler = B::ConnectionHandler;
+    type ToSwarm = B::ToSwarm;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.inner.handle_pending_inbound_connection(connection_id, local_addr, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let handler = self.inner.handle_established_inbound_connection(connection_id, peer, local_addr, remote_addr)?;
+        self.created.insert(connection_id);
+        Ok(handler)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        addresses: &[Multiaddr],
+        effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        self.inner.handle_pending_outbound_connection(connection_id, maybe_peer, addresses, effective_role)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        let handler = self.inner.handle_established_outbound_connection(connection_id, peer, addr, role_override)?;
+        self.created.insert(connection_id);
+        Ok(handler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(e) => {
+                if !self.created.remove(&e.connection_id) {
+                    return;
+                }
+                let connections = self.known.entry(e.peer_id).or_default();
+                connections.insert(e.connection_id);
+                self.inner.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                    other_established: connections.len() - 1,
+                    ..e
+                }));
+            },
+            FromSwarm::ConnectionClosed(e) => {
+                let Some(connections) = self.known.get_mut(&e.peer_id) else {
+                    return;
+                };
+                if !connections.remove(&e.connection_id) {
+                    return;
+                }
+                let remaining_established = connections.len();
+                if remaining_established == 0 {
+                    self.known.remove(&e.peer_id);
+                }
+                self.inner.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed { remaining_established, ..e }));
+            },
+            FromSwarm::AddressChange(e) if !self.is_known(&e.peer_id, &e.connection_id) => {},
+            event => self.inner.on_swarm_event(event),
+        }
+    }
+
+    fn on_connection_handler_event(&mut self, peer_id: PeerId, connection_id: ConnectionId, event: THandlerOutEvent<Self>) {
+        if self.is_known(&peer_id, &connection_id) {
+            self.inner.on_connection_handler_event(peer_id, connection_id, event);
+        }
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.inner.poll(cx)
+    }
 }
@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use libp2p::PeerId;
+use tracing::warn;
+
+// Blocked peers, persisted as one PeerId per line in <storage_path>/blocked_peers_<instance>.txt.
+// Lines starting with '#' are ignored
+pub struct BlockList {
+    path: PathBuf,
+    peers: HashSet<PeerId>,
+    modified: Option<SystemTime>,
+}
+
+impl BlockList {
+    pub fn load(storage_path: &str, instance_name: &str) -> Self {
+        let mut path = PathBuf::from(storage_path);
+        path.push(format!("blocked_peers_{}.txt", instance_name));
+
+        let mut list = Self { path, peers: HashSet::new(), modified: None };
+        list.peers = list.read();
+        list
+    }
+
+    pub fn contains(&self, peer: &PeerId) -> bool {
+        self.peers.contains(peer)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.peers.iter()
+    }
+
+    // Returns false if the peer was already blocked
+    pub fn block(&mut self, peer: PeerId) -> bool {
+        let added = self.peers.insert(peer);
+        if added {
+            self.save();
+        }
+        added
+    }
+
+    // Returns false if the peer was not blocked
+    pub fn unblock(&mut self, peer: &PeerId) -> bool {
+        let removed = self.peers.remove(peer);
+        if removed {
+            self.save();
+        }
+        removed
+    }
+
+    // Picks up edits made to the file by hand. Returns (blocked, unblocked) if it changed
+    pub fn reload(&mut self) -> Option<(Vec<PeerId>, Vec<PeerId>)> {
+        if self.modified_on_disk() == self.modified {
+            return None;
+        }
+        let peers = self.read();
+        let blocked: Vec<PeerId> = peers.difference(&self.peers).copied().collect();
+        let unblocked: Vec<PeerId> = self.peers.difference(&peers).copied().collect();
+        self.peers = peers;
+        Some((blocked, unblocked))
+    }
+
+    fn modified_on_disk(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    fn read(&mut self) -> HashSet<PeerId> {
+        self.modified = self.modified_on_disk();
+        let Ok(text) = fs::read_to_string(&self.path) else {
+            return HashSet::new();
+        };
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| match line.parse() {
+                Ok(peer) => Some(peer),
+                Err(_) => {
+                    warn!("[BLOCK] Ignoring invalid peer id in {:?}: {}", self.path, line);
+                    None
+                },
+            })
+            .collect()
+    }
+
+    fn save(&mut self) {
+        let mut peers: Vec<String> = self.peers.iter().map(|p| p.to_string()).collect();
+        peers.sort();
+        let text = peers.iter().map(|p| format!("{}\n", p)).collect::<String>();
+        if let Err(e) = fs::write(&self.path, text) {
+            warn!("[BLOCK] Failed to save {:?}: {}", self.path, e);
+        }
+        self.modified = self.modified_on_disk();
+    }
+}
@@ -0,0 +1,61 @@
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+
+// Relay address reduced to `<transport stack>/p2p/<relay id>`. Everything up to the relay's
+// /p2p is kept (ip4/ip6/dns*, tcp/udp, quic-v1, ws/wss, webrtc-direct...), a trailing
+// /p2p-circuit is dropped. None if the address names another peer or has no transport part
+pub fn relay_base(relay_addr: &Multiaddr, relay_id: PeerId) -> Option<Multiaddr> {
+    let mut base = Multiaddr::empty();
+    for protocol in relay_addr.iter() {
+        match protocol {
+            Protocol::P2p(id) if id == relay_id => break,
+            // Nested circuit or a different peer: not a physical address of this relay
+            Protocol::P2p(_) | Protocol::P2pCircuit => return None,
+            other => base.push(other),
+        }
+    }
+
+    if base.is_empty() {
+        return None;
+    }
+    Some(base.with(Protocol::P2p(relay_id)))
+}
+
+// Address we listen on to hold a reservation on the relay
+pub fn reservation_address(relay_addr: &Multiaddr, relay_id: PeerId) -> Option<Multiaddr> {
+    Some(relay_base(relay_addr, relay_id)?.with(Protocol::P2pCircuit))
+}
+
+// `<relay>/p2p-circuit/p2p/<target>`
+pub fn circuit_address(relay_addr: &Multiaddr, relay_id: PeerId, target: PeerId) -> Option<Multiaddr> {
+    if relay_id == target {
+        return None;
+    }
+    Some(reservation_address(relay_addr, relay_id)?.with(Protocol::P2p(target)))
+}
+
+// Circuit addresses to `target` through every known relay address, in the given order and
+// without duplicates. Unusable relay addresses are skipped
+pub fn circuit_addresses<'a>(
+    relay_addrs: impl IntoIterator<Item = (PeerId, &'a Multiaddr)>,
+    target: PeerId,
+) -> Vec<Multiaddr> {
+    let mut addrs: Vec<Multiaddr> = Vec::new();
+    for (relay_id, relay_addr) in relay_addrs {
+        if let Some(addr) = circuit_address(relay_addr, relay_id, target) {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
+        }
+    }
+    addrs
+}
+
+// Whether a relay's self-reported listen address is worth dialing from elsewhere
+pub fn is_dialable_relay_address(addr: &Multiaddr) -> bool {
+    addr.iter().all(|p| match p {
+        Protocol::Ip4(ip) => !ip.is_loopback() && !ip.is_unspecified(),
+        Protocol::Ip6(ip) => !ip.is_loopback() && !ip.is_unspecified(),
+        Protocol::P2pCircuit => false,
+        _ => true,
+    })
+}
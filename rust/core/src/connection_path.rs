@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use libp2p::{multiaddr::Protocol, swarm::ConnectionId, Multiaddr, PeerId};
+
+use crate::hole_punch::transport_name;
+
+// DCUtR gets this long to report back. A peer without the protocol never does
+pub const HOLE_PUNCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+// How we currently reach a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathState {
+    // Only through a relay circuit
+    Relayed,
+    // Relayed while DCUtR tries to open a direct connection
+    HolePunching,
+    Direct,
+    // Still relayed, the upgrade to a direct connection failed
+    Failed,
+}
+
+impl PathState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PathState::Relayed => "RELAYED",
+            PathState::HolePunching => "HOLE_PUNCHING",
+            PathState::Direct => "DIRECT",
+            PathState::Failed => "FAILED",
+        }
+    }
+}
+
+struct PeerPath {
+    state: PathState,
+    since: Instant,
+    connections: HashMap<ConnectionId, Link>,
+}
+
+struct Link {
+    relayed: bool,
+    transport: &'static str,
+}
+
+// Connection path per connected peer: relayed -> hole punching -> direct / failed.
+// Each method returns the new state if it changed
+#[derive(Default)]
+pub struct ConnectionPaths {
+    peers: HashMap<PeerId, PeerPath>,
+}
+
+impl ConnectionPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `hole_punching`: DCUtR is enabled and will try to upgrade a relayed connection
+    pub fn on_established(&mut self, peer: PeerId, connection: ConnectionId, addr: &Multiaddr, hole_punching: bool) -> Option<PathState> {
+        let relayed = addr.iter().any(|p| p == Protocol::P2pCircuit);
+        let entry = self.peers.entry(peer).or_insert_with(|| PeerPath {
+            state: PathState::Relayed,
+            since: Instant::now(),
+            connections: HashMap::new(),
+        });
+        let is_new = entry.connections.is_empty();
+        entry.connections.insert(connection, Link { relayed, transport: transport_name(addr) });
+        let next = match (relayed, entry.state) {
+            (false, _) => PathState::Direct,
+            // A direct connection is already open
+            (true, PathState::Direct) if !is_new => PathState::Direct,
+            (true, _) if hole_punching => PathState::HolePunching,
+            (true, _) => PathState::Relayed,
+        };
+        Self::transition(entry, next, is_new)
+    }
+
+    // DCUtR finished for the peer
+    pub fn on_upgrade(&mut self, peer: &PeerId, success: bool) -> Option<PathState> {
+        let entry = self.peers.get_mut(peer)?;
+        let next = match (success, entry.state) {
+            (true, _) => PathState::Direct,
+            (false, PathState::HolePunching) => PathState::Failed,
+            (false, state) => state,
+        };
+        Self::transition(entry, next, false)
+    }
+
+    // None once the last connection is gone, the peer is forgotten then
+    pub fn on_closed(&mut self, peer: &PeerId, connection: ConnectionId) -> Option<PathState> {
+        let entry = self.peers.get_mut(peer)?;
+        entry.connections.remove(&connection);
+        if entry.connections.is_empty() {
+            self.peers.remove(peer);
+            return None;
+        }
+        // Lost the direct connection, what is left is relayed
+        let next = if entry.connections.values().any(|link| !link.relayed) {
+            PathState::Direct
+        } else if entry.state == PathState::Direct {
+            PathState::Relayed
+        } else {
+            entry.state
+        };
+        Self::transition(entry, next, false)
+    }
+
+    // Peers that stayed in HolePunching longer than HOLE_PUNCH_TIMEOUT, moved back to Relayed
+    pub fn expire_hole_punching(&mut self, now: Instant) -> Vec<PeerId> {
+        let mut expired = Vec::new();
+        for (peer, entry) in self.peers.iter_mut() {
+            if entry.state == PathState::HolePunching && now.duration_since(entry.since) >= HOLE_PUNCH_TIMEOUT {
+                Self::transition(entry, PathState::Relayed, false);
+                expired.push(*peer);
+            }
+        }
+        expired
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<PathState> {
+        self.peers.get(peer).map(|p| p.state)
+    }
+
+    // Transport of one of the peer's connections (TCP, QUIC, ...)
+    pub fn transport(&self, peer: &PeerId, connection: ConnectionId) -> Option<&'static str> {
+        self.peers.get(peer)?.connections.get(&connection).map(|link| link.transport)
+    }
+
+    // How long the peer has been in its current state
+    pub fn since(&self, peer: &PeerId) -> Option<Instant> {
+        self.peers.get(peer).map(|p| p.since)
+    }
+
+    fn transition(entry: &mut PeerPath, next: PathState, force: bool) -> Option<PathState> {
+        if entry.state == next && !force {
+            return None;
+        }
+        entry.state = next;
+        entry.since = Instant::now();
+        Some(next)
+    }
+}
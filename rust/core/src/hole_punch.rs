@@ -0,0 +1,190 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::Serialize;
+
+use crate::reconnect::Backoff;
+
+// Retries after DCUtR gave up, each on a fresh relayed connection
+pub const MAX_HOLE_PUNCH_RETRIES: u32 = 5;
+const RETRY_INITIAL: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(600);
+
+// Transport of a direct connection, for the statistics
+pub fn transport_name(addr: &Multiaddr) -> &'static str {
+    let mut name = "OTHER";
+    for protocol in addr.iter() {
+        match protocol {
+            Protocol::QuicV1 | Protocol::Quic => return "QUIC",
+            Protocol::WebRTCDirect => return "WEBRTC",
+            Protocol::Ws(_) | Protocol::Wss(_) => return "WEBSOCKET",
+            Protocol::Tcp(_) => name = "TCP",
+            _ => {},
+        }
+    }
+    name
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Counters {
+    pub attempts: u64,
+    pub successes: u64,
+    pub failures: u64,
+    // Total time of the attempts counted here, for the average
+    #[serde(skip)]
+    total: Duration,
+    pub avg_ms: u64,
+}
+
+impl Counters {
+    fn record(&mut self, success: bool, took: Duration) {
+        self.attempts += 1;
+        if success {
+            self.successes += 1;
+        } else {
+            self.failures += 1;
+        }
+        self.total += took;
+        self.avg_ms = (self.total.as_millis() / self.attempts as u128) as u64;
+    }
+
+    pub fn success_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        self.successes as f64 / self.attempts as f64
+    }
+}
+
+// Snapshot returned by the stats query
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct HolePunchSummary {
+    pub total: Counters,
+    pub retries: u64,
+    // By our own AutoNAT status when the attempt started (PUBLIC, PRIVATE, UNKNOWN).
+    // The remote peer's NAT type is not known
+    pub by_local_nat: BTreeMap<String, Counters>,
+    // Successes by transport of the direct connection, failures by transport of the relayed one
+    // (DCUtR does not report which addresses it tried)
+    pub by_transport: BTreeMap<String, Counters>,
+}
+
+struct Pending {
+    started: Instant,
+    // Our NAT status when the attempt started
+    nat: &'static str,
+    // Transport of the relayed connection
+    transport: &'static str,
+}
+
+struct Retry {
+    backoff: Backoff,
+    due: Option<Instant>,
+}
+
+// Timing of each hole punch, success counters and the retry schedule of peers whose upgrade failed
+#[derive(Default)]
+pub struct HolePunching {
+    pending: HashMap<PeerId, Pending>,
+    retries: HashMap<PeerId, Retry>,
+    summary: HolePunchSummary,
+}
+
+impl HolePunching {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A relayed connection over `transport` came up and DCUtR started
+    pub fn on_started(&mut self, peer: PeerId, nat: &'static str, transport: &'static str) {
+        self.pending.entry(peer).or_insert(Pending { started: Instant::now(), nat, transport });
+    }
+
+    // `transport` of the direct connection. Returns how long the attempt took, None if it was not seen starting
+    pub fn on_succeeded(&mut self, peer: &PeerId, transport: &'static str) -> Option<Duration> {
+        self.retries.remove(peer);
+        self.finish(peer, Some(transport))
+    }
+
+    // Returns how long the attempt took and when to retry, None once the retries are used up
+    pub fn on_failed(&mut self, peer: PeerId) -> (Option<Duration>, Option<Duration>) {
+        let took = self.finish(&peer, None);
+        let retry = self.retries.entry(peer).or_insert_with(|| Retry {
+            backoff: Backoff::new(RETRY_INITIAL, RETRY_MAX),
+            due: None,
+        });
+        // Several failures of the same attempt, one retry is enough
+        if let Some(at) = retry.due {
+            return (took, Some(at.saturating_duration_since(Instant::now())));
+        }
+        if retry.backoff.attempt() >= MAX_HOLE_PUNCH_RETRIES {
+            return (took, None);
+        }
+        let delay = retry.backoff.next_delay();
+        retry.due = Some(Instant::now() + delay);
+        (took, Some(delay))
+    }
+
+    // Peers whose retry is due. The caller opens a new relayed connection
+    pub fn due_retries(&mut self, now: Instant) -> Vec<PeerId> {
+        let mut due = Vec::new();
+        for (peer, retry) in self.retries.iter_mut() {
+            if retry.due.is_some_and(|at| at <= now) {
+                retry.due = None;
+                due.push(*peer);
+            }
+        }
+        self.summary.retries += due.len() as u64;
+        due
+    }
+
+    // DCUtR never reported back or the peer disconnected, the attempt is not counted.
+    // A scheduled retry stays, it opens a new relayed connection
+    pub fn abandon(&mut self, peer: &PeerId) {
+        self.pending.remove(peer);
+    }
+
+    // The peer was blocked, attempts in flight and pending retries are dropped
+    pub fn forget(&mut self, peer: &PeerId) {
+        self.pending.remove(peer);
+        self.retries.remove(peer);
+    }
+
+    pub fn summary(&self) -> &HolePunchSummary {
+        &self.summary
+    }
+
+    // `direct`: transport of the direct connection, None if the attempt failed
+    fn finish(&mut self, peer: &PeerId, direct: Option<&'static str>) -> Option<Duration> {
+        let pending = self.pending.remove(peer)?;
+        let took = pending.started.elapsed();
+        let success = direct.is_some();
+        let transport = direct.unwrap_or(pending.transport);
+        self.summary.total.record(success, took);
+        self.summary.by_local_nat.entry(pending.nat.to_string()).or_default().record(success, took);
+        self.summary.by_transport.entry(transport.to_string()).or_default().record(success, took);
+        Some(took)
+    }
+}
+
+impl fmt::Display for HolePunchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "hole punching {}/{} succeeded ({:.0}%, avg {} ms, {} retries)",
+            self.total.successes,
+            self.total.attempts,
+            self.total.success_rate() * 100.0,
+            self.total.avg_ms,
+            self.retries,
+        )?;
+        for (nat, counters) in &self.by_local_nat {
+            write!(f, ", local {} {}/{}", nat, counters.successes, counters.attempts)?;
+        }
+        for (transport, counters) in &self.by_transport {
+            write!(f, ", {} {}/{}", transport, counters.successes, counters.attempts)?;
+        }
+        Ok(())
+    }
+}
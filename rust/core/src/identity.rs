@@ -1,6 +1,8 @@
-use libp2p::identity;
+use libp2p::{identity, pnet::PreSharedKey};
+use libp2p_webrtc::tokio::Certificate;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
 pub fn get_or_create_identity(storage_path: &str, instance_name: &str) -> identity::Keypair {
     let mut path = PathBuf::from(storage_path);
@@ -19,4 +21,55 @@ pub fn get_or_create_identity(storage_path: &str, instance_name: &str) -> identi
         let _ = fs::write(&path, bytes);
     }
     keypair
+}
+
+// WebRTC certificate, persisted so the /certhash of our webrtc-direct address stays stable
+pub fn get_or_create_webrtc_certificate(storage_path: &str, instance_name: &str) -> Certificate {
+    let mut path = PathBuf::from(storage_path);
+    path.push(format!("webrtc_cert_{}.pem", instance_name));
+
+    // On 32-bit ARM the webrtc crate limits certificates to 2 days, renew them daily
+    let stale = cfg!(target_arch = "arm") && fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > Duration::from_secs(24 * 3600));
+
+    if path.exists() && !stale {
+        if let Ok(pem) = fs::read_to_string(&path) {
+            if let Ok(certificate) = Certificate::from_pem(&pem) {
+                return certificate;
+            }
+        }
+    }
+
+    let certificate = Certificate::generate(&mut rand::thread_rng()).expect("Failed to generate WebRTC certificate");
+    let _ = fs::write(&path, certificate.serialize_pem());
+    certificate
+}
+
+// Private network key: the configured value (swarm.key contents or bare 64 hex chars) wins,
+// otherwise <storage_path>/swarm.key. Ok(None) means a public network
+pub fn load_pre_shared_key(storage_path: &str, configured: &str) -> Result<Option<PreSharedKey>, String> {
+    let mut path = PathBuf::from(storage_path);
+    path.push("swarm.key");
+
+    let (source, text) = if !configured.trim().is_empty() {
+        ("config".to_string(), configured.to_string())
+    } else if path.exists() {
+        let text = fs::read_to_string(&path).map_err(|e| format!("Cannot read {:?}: {}", path, e))?;
+        (format!("{:?}", path), text)
+    } else {
+        return Ok(None);
+    };
+
+    let text = text.trim();
+    let key_file = if text.starts_with("/key/") {
+        text.to_string()
+    } else {
+        format!("/key/swarm/psk/1.0.0/\n/base16/\n{}", text)
+    };
+    key_file.parse::<PreSharedKey>()
+        .map(Some)
+        .map_err(|e| format!("Invalid pre-shared key in {}: {:?} (expected 64 hex characters)", source, e))
 }
\ No newline at end of file
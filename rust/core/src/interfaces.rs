@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+// Network changes closer together than this are handled once
+const MIN_CHANGE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Non-loopback addresses of the local interfaces, to notice when the device switches networks
+// (Wi-Fi to mobile data, a new DHCP lease, a VPN coming up...)
+pub struct InterfaceWatch {
+    addrs: BTreeSet<IpAddr>,
+    last_change: Option<Instant>,
+    // A change came in too soon after the last one and waits for `deferred_due`
+    deferred: bool,
+}
+
+fn local_addrs() -> BTreeSet<IpAddr> {
+    match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces.into_iter()
+            .filter(|iface| !iface.is_loopback())
+            .map(|iface| iface.ip())
+            .collect(),
+        Err(e) => {
+            warn!("[NETWORK] Cannot list network interfaces: {}", e);
+            BTreeSet::new()
+        },
+    }
+}
+
+impl InterfaceWatch {
+    pub fn new() -> Self {
+        Self::with_addrs(local_addrs())
+    }
+
+    // Starts from the given snapshot instead of the current interfaces
+    pub fn with_addrs(addrs: BTreeSet<IpAddr>) -> Self {
+        Self { addrs, last_change: None, deferred: false }
+    }
+
+    // True if the addresses differ from the last snapshot
+    pub fn poll(&mut self) -> bool {
+        self.update(local_addrs())
+    }
+
+    // Replaces the snapshot, true if `addrs` differ from it
+    pub fn update(&mut self, addrs: BTreeSet<IpAddr>) -> bool {
+        if addrs == self.addrs {
+            return false;
+        }
+        debug!("[NETWORK] Interface addresses changed: {:?} -> {:?}", self.addrs, addrs);
+        self.addrs = addrs;
+        true
+    }
+
+    // False if a change was handled moments ago, this one is deferred then. Takes a fresh snapshot,
+    // so polling does not report the same change again
+    pub fn should_handle(&mut self, now: Instant) -> bool {
+        self.addrs = local_addrs();
+        if self.too_soon(now) {
+            // The second half of a handover (Wi-Fi gone, mobile data up) must not be lost
+            self.deferred = true;
+            return false;
+        }
+        self.last_change = Some(now);
+        self.deferred = false;
+        true
+    }
+
+    // True once a deferred change may be handled. The caller reports it again through `should_handle`
+    pub fn deferred_due(&mut self, now: Instant) -> bool {
+        if !self.deferred || self.too_soon(now) {
+            return false;
+        }
+        self.deferred = false;
+        true
+    }
+
+    fn too_soon(&self, now: Instant) -> bool {
+        self.last_change.is_some_and(|at| now.duration_since(at) < MIN_CHANGE_INTERVAL)
+    }
+}
+
+impl Default for InterfaceWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use libp2p::PeerId;
+use tracing::warn;
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Wall-clock time each contact was last seen online, persisted in
+// <storage_path>/last_seen_<instance>.json so it survives restarts and refreshes
+pub struct LastSeen {
+    path: PathBuf,
+    seen: HashMap<PeerId, u64>,
+    dirty: bool,
+}
+
+impl LastSeen {
+    pub fn load(storage_path: &str, instance_name: &str) -> Self {
+        let mut path = PathBuf::from(storage_path);
+        path.push(format!("last_seen_{}.json", instance_name));
+
+        let stored: HashMap<String, u64> = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("[LAST SEEN] Ignoring corrupt {:?}: {}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        let seen = stored.into_iter()
+            .filter_map(|(peer, secs)| Some((peer.parse().ok()?, secs)))
+            .collect();
+        Self { path, seen, dirty: false }
+    }
+
+    // Seen online now, returns the unix seconds stored
+    pub fn touch(&mut self, peer: PeerId) -> u64 {
+        let secs = now();
+        self.seen.insert(peer, secs);
+        self.dirty = true;
+        secs
+    }
+
+    // Unix seconds, None if never seen or the peer does not share it
+    pub fn get(&self, peer: &PeerId) -> Option<u64> {
+        self.seen.get(peer).copied()
+    }
+
+    // The peer asked not to share its last-seen
+    pub fn forget(&mut self, peer: &PeerId) {
+        if self.seen.remove(peer).is_some() {
+            self.dirty = true;
+        }
+    }
+
+    pub fn save_if_dirty(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let stored: HashMap<String, u64> = self.seen.iter().map(|(p, s)| (p.to_string(), *s)).collect();
+        match serde_json::to_string(&stored) {
+            Ok(text) => {
+                if let Err(e) = fs::write(&self.path, text) {
+                    warn!("[LAST SEEN] Failed to save {:?}: {}", self.path, e);
+                    return;
+                }
+                self.dirty = false;
+            },
+            Err(e) => warn!("[LAST SEEN] Failed to serialize: {}", e),
+        }
+    }
+}
@@ -2,3 +2,21 @@ pub mod behaviour;
 pub mod transport;
 pub mod identity;
 pub mod logger;
+pub mod reconnect;
+pub mod relay_discovery;
+pub mod port_mapping;
+pub mod circuit;
+pub mod network;
+pub mod socks;
+pub mod limits;
+pub mod block_list;
+pub mod address_book;
+pub mod record_store;
+pub mod rendezvous;
+pub mod presence;
+pub mod peer_table;
+pub mod last_seen;
+pub mod connection_path;
+pub mod hole_punch;
+pub mod interfaces;
+pub mod power;
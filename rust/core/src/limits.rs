@@ -0,0 +1,148 @@
+use std::fmt;
+use libp2p::{
+    connection_limits::{self, ConnectionLimits, Exceeded},
+    memory_connection_limits::{self, MemoryUsageLimitExceeded},
+    swarm::{behaviour::toggle::Toggle, ConnectionDenied, DialError, ListenError},
+};
+
+// Caps on raw connections and memory, checked before a connection is accepted.
+// `None` means unlimited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_established: Option<u32>,
+    pub max_established_per_peer: Option<u32>,
+    pub max_pending_incoming: Option<u32>,
+    // Refuse new connections while the process uses more than this fraction of system RAM
+    pub max_memory_fraction: Option<f64>,
+}
+
+impl Limits {
+    // Phones and desktops: a handful of relays plus the chat peers
+    pub fn node() -> Self {
+        Self {
+            max_established: Some(128),
+            max_established_per_peer: Some(4),
+            max_pending_incoming: Some(32),
+            max_memory_fraction: Some(0.9),
+        }
+    }
+
+    // Every client keeps a connection to the relay, circuits ride on top of them
+    pub fn relay() -> Self {
+        Self {
+            max_established: Some(4096),
+            max_established_per_peer: Some(8),
+            max_pending_incoming: Some(256),
+            max_memory_fraction: Some(0.8),
+        }
+    }
+
+    // P2P_MAX_CONNECTIONS, P2P_MAX_CONNECTIONS_PER_PEER, P2P_MAX_PENDING_INCOMING and
+    // P2P_MAX_MEMORY_PERCENT override the defaults, 0 disables a limit
+    pub fn with_env_overrides(mut self) -> Self {
+        fn read<T: std::str::FromStr>(name: &str) -> Option<T> {
+            let value = std::env::var(name).ok()?;
+            match value.trim().parse() {
+                Ok(parsed) => Some(parsed),
+                Err(_) => {
+                    tracing::warn!("[LIMITS] Ignoring invalid {}={}", name, value);
+                    None
+                },
+            }
+        }
+
+        if let Some(v) = read::<u32>("P2P_MAX_CONNECTIONS") {
+            self.max_established = (v > 0).then_some(v);
+        }
+        if let Some(v) = read::<u32>("P2P_MAX_CONNECTIONS_PER_PEER") {
+            self.max_established_per_peer = (v > 0).then_some(v);
+        }
+        if let Some(v) = read::<u32>("P2P_MAX_PENDING_INCOMING") {
+            self.max_pending_incoming = (v > 0).then_some(v);
+        }
+        if let Some(v) = read::<f64>("P2P_MAX_MEMORY_PERCENT") {
+            self.max_memory_fraction = (v > 0.0).then_some(v.min(100.0) / 100.0);
+        }
+        self
+    }
+
+    pub fn connection_limits(&self) -> connection_limits::Behaviour {
+        connection_limits::Behaviour::new(ConnectionLimits::default()
+            .with_max_established(self.max_established)
+            .with_max_established_per_peer(self.max_established_per_peer)
+            .with_max_pending_incoming(self.max_pending_incoming))
+    }
+
+    pub fn memory_limits(&self) -> Toggle<memory_connection_limits::Behaviour> {
+        self.max_memory_fraction
+            .map(memory_connection_limits::Behaviour::with_max_percentage)
+            .into()
+    }
+}
+
+impl fmt::Display for Limits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn show<T: fmt::Display>(value: Option<T>) -> String {
+            value.map_or_else(|| "unlimited".to_string(), |v| v.to_string())
+        }
+        write!(
+            f,
+            "max connections {}, per peer {}, pending incoming {}, memory {}",
+            show(self.max_established),
+            show(self.max_established_per_peer),
+            show(self.max_pending_incoming),
+            show(self.max_memory_fraction.map(|m| format!("{:.0}%", m * 100.0))),
+        )
+    }
+}
+
+// Connections refused by our limits, reported with the periodic metrics log
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LimitMetrics {
+    pub incoming_rejected: u64,
+    pub outgoing_rejected: u64,
+    pub memory_rejected: u64,
+}
+
+impl LimitMetrics {
+    // Counts an incoming connection refused by a limit, returns the reason
+    pub fn record_incoming(&mut self, error: &ListenError) -> Option<String> {
+        let ListenError::Denied { cause } = error else {
+            return None;
+        };
+        let reason = self.classify(cause)?;
+        self.incoming_rejected += 1;
+        Some(reason)
+    }
+
+    // Counts a dial refused by a limit, returns the reason
+    pub fn record_outgoing(&mut self, error: &DialError) -> Option<String> {
+        let DialError::Denied { cause } = error else {
+            return None;
+        };
+        let reason = self.classify(cause)?;
+        self.outgoing_rejected += 1;
+        Some(reason)
+    }
+
+    fn classify(&mut self, cause: &ConnectionDenied) -> Option<String> {
+        if let Some(exceeded) = cause.downcast_ref::<Exceeded>() {
+            return Some(exceeded.to_string());
+        }
+        if let Some(exceeded) = cause.downcast_ref::<MemoryUsageLimitExceeded>() {
+            self.memory_rejected += 1;
+            return Some(exceeded.to_string());
+        }
+        None
+    }
+}
+
+impl fmt::Display for LimitMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rejected incoming {}, outgoing {} (memory {})",
+            self.incoming_rejected, self.outgoing_rejected, self.memory_rejected,
+        )
+    }
+}
@@ -12,7 +12,7 @@ pub fn init_p2p_logging(storage_path: &str, peer_id: &PeerId) -> WorkerGuard {
     }
 
     // Log config
-    let file_name = format!("{}.log", peer_id.to_string());
+    let file_name = format!("{}.log", peer_id);
     let file_appender = tracing_appender::rolling::never(log_dir, file_name);
     let (non_blocking_file, guard) = tracing_appender::non_blocking(file_appender);
 
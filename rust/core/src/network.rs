@@ -0,0 +1,111 @@
+use libp2p::{gossipsub, kad::RecordKey, rendezvous::{Namespace, MAX_NAMESPACE}, PeerId, StreamProtocol};
+
+// Names used by the default (unnamed) network. Kept as they were so existing
+// deployments keep talking to each other
+const DEFAULT_TOPIC: &str = "p2p-chat-global";
+const PROTOCOL_PREFIX: &str = "/p2p_msg";
+const RENDEZVOUS_PREFIX: &str = "p2p-chat";
+
+// Network namespace. Every topic and protocol name is derived from the network id, so
+// independent deployments (e.g. staging and production) never mix
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Network {
+    id: String,
+}
+
+impl Network {
+    // An empty id selects the default network. The id goes into protocol names, topics and DHT
+    // keys, so only [A-Za-z0-9._-] is allowed, and it has to fit in a rendezvous namespace
+    pub fn new(network_id: &str) -> Result<Self, String> {
+        let network = Self { id: network_id.trim().to_string() };
+        if let Some(c) = network.id.chars().find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))) {
+            return Err(format!("Network id contains '{}', only letters, digits, '.', '_' and '-' are allowed", c));
+        }
+        if network.rendezvous_namespace(None).is_none() {
+            let max = MAX_NAMESPACE - RENDEZVOUS_PREFIX.len() - 1;
+            return Err(format!("Network id is too long ({} bytes, at most {})", network.id.len(), max));
+        }
+        Ok(network)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    // "/p2p_msg" for the default network, "/p2p_msg/<id>" otherwise
+    fn prefix(&self) -> String {
+        if self.id.is_empty() {
+            PROTOCOL_PREFIX.to_string()
+        } else {
+            format!("{}/{}", PROTOCOL_PREFIX, self.id)
+        }
+    }
+
+    pub fn topic_name(&self) -> String {
+        if self.id.is_empty() {
+            DEFAULT_TOPIC.to_string()
+        } else {
+            format!("{}-{}", DEFAULT_TOPIC, self.id)
+        }
+    }
+
+    pub fn topic(&self) -> gossipsub::IdentTopic {
+        gossipsub::IdentTopic::new(self.topic_name())
+    }
+
+    // Gossipsub config with a namespaced protocol id, so meshes of different networks
+    // cannot form even when peers end up connected
+    pub fn gossipsub_config(&self) -> gossipsub::ConfigBuilder {
+        let mut builder = gossipsub::ConfigBuilder::default();
+        if !self.id.is_empty() {
+            builder.protocol_id_prefix(format!("{}/meshsub", self.prefix()));
+        }
+        builder
+    }
+
+    pub fn kad_protocol(&self) -> StreamProtocol {
+        StreamProtocol::try_from_owned(format!("{}/kad/1.0.0", self.prefix())).expect("Protocol names start with /")
+    }
+
+    // Identify protocol version, peers announcing another one belong to another network
+    pub fn identify_protocol(&self) -> String {
+        format!("{}/id/1.0.0", self.prefix())
+    }
+
+    pub fn relay_provider_key_name(&self) -> String {
+        format!("{}/relays/1.0.0", self.prefix())
+    }
+
+    // DHT key under which relay servers of this network register as providers
+    pub fn relay_provider_key(&self) -> RecordKey {
+        RecordKey::new(&self.relay_provider_key_name())
+    }
+
+    fn presence_prefix(&self) -> String {
+        format!("{}/presence/", self.prefix())
+    }
+
+    // DHT key of a peer's signed presence record
+    pub fn presence_key(&self, peer: &PeerId) -> RecordKey {
+        RecordKey::new(&format!("{}{}", self.presence_prefix(), peer))
+    }
+
+    pub fn is_presence_key(&self, key: &RecordKey) -> bool {
+        key.as_ref().starts_with(self.presence_prefix().as_bytes())
+    }
+
+    // Rendezvous namespace of the whole network ("p2p-chat[/<id>]") or of one room in it.
+    // None if the room name makes it longer than rendezvous allows
+    pub fn rendezvous_namespace(&self, room: Option<&str>) -> Option<Namespace> {
+        let mut name = RENDEZVOUS_PREFIX.to_string();
+        if !self.id.is_empty() {
+            name.push('/');
+            name.push_str(&self.id);
+        }
+        if let Some(room) = room {
+            name.push_str("/room/");
+            name.push_str(room);
+        }
+        Namespace::new(name).ok()
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+use libp2p::PeerId;
+
+use crate::presence::{Presence, PresenceStatus};
+
+// Failed pings in a row after which a connected peer counts as gone
+pub const MAX_PING_FAILURES: u32 = 3;
+
+// Where we learned that a peer is online
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DiscoverySource {
+    Mdns,
+    Kademlia,
+    Gossip,
+    Connection,
+    Presence,
+}
+
+impl DiscoverySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscoverySource::Mdns => "MDNS",
+            DiscoverySource::Kademlia => "KADEMLIA",
+            DiscoverySource::Gossip => "GOSSIP",
+            DiscoverySource::Connection => "CONNECTION",
+            DiscoverySource::Presence => "PRESENCE",
+        }
+    }
+
+    // Vouches for the peer until `online_until`. Connections and mDNS entries are taken away by their own events
+    fn expires(&self) -> bool {
+        !matches!(self, DiscoverySource::Connection | DiscoverySource::Mdns)
+    }
+}
+
+// Why a peer went offline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepartureReason {
+    // Presence ran out and no newer record was found
+    Timeout,
+    Closed,
+    MdnsExpired,
+    // Evicted from a full Kademlia bucket
+    Unroutable,
+    Blocked,
+    // Too many failed pings in a row
+    Unresponsive,
+    // Belongs to another network
+    Foreign,
+}
+
+impl DepartureReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DepartureReason::Timeout => "TIMEOUT",
+            DepartureReason::Closed => "CLOSED",
+            DepartureReason::MdnsExpired => "MDNS_EXPIRED",
+            DepartureReason::Unroutable => "UNROUTABLE",
+            DepartureReason::Blocked => "BLOCKED",
+            DepartureReason::Unresponsive => "UNRESPONSIVE",
+            DepartureReason::Foreign => "FOREIGN",
+        }
+    }
+
+    // True if the reason takes the source away
+    fn withdraws(&self, source: DiscoverySource) -> bool {
+        match self {
+            DepartureReason::Timeout => source.expires(),
+            // Only the connection itself, the other sources vouch until they lapse or their own event
+            DepartureReason::Closed => source == DiscoverySource::Connection,
+            DepartureReason::MdnsExpired => source == DiscoverySource::Mdns,
+            DepartureReason::Unroutable => source == DiscoverySource::Kademlia,
+            DepartureReason::Blocked | DepartureReason::Unresponsive | DepartureReason::Foreign => true,
+        }
+    }
+}
+
+// What we know about one online peer
+#[derive(Debug, Clone)]
+pub struct PeerEntry {
+    // Sources that currently vouch for the peer, it is online while any is left
+    pub sources: BTreeSet<DiscoverySource>,
+    // Until when the expiring sources (Kademlia, gossip, presence record) vouch for the peer
+    pub online_until: Instant,
+    // Round trip of the last successful ping
+    pub rtt: Option<Duration>,
+    // Failed pings in a row, reset by a successful one
+    pub consecutive_failures: u32,
+    pub ping_failures: u32,
+    // Status from the peer's presence record or announcement, None until one arrived
+    pub presence: Option<Presence>,
+    presence_requested: bool,
+    // From the presence record, not shared until one opts in
+    pub shares_last_seen: bool,
+}
+
+impl PeerEntry {
+    // Connected but announced INVISIBLE, shown as offline
+    pub fn is_invisible(&self) -> bool {
+        self.presence.as_ref().is_some_and(|p| p.status == PresenceStatus::Invisible)
+    }
+
+    // Invisible peers are not recorded either, that would give them away
+    pub fn shows_last_seen(&self) -> bool {
+        self.shares_last_seen && !self.is_invisible()
+    }
+}
+
+// Peers shown as online in the UI
+#[derive(Default)]
+pub struct PeerTable {
+    peers: HashMap<PeerId, PeerEntry>,
+}
+
+impl PeerTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // True if the peer was not online before
+    pub fn mark_online(&mut self, peer: PeerId, source: DiscoverySource, until: Instant) -> bool {
+        match self.peers.get_mut(&peer) {
+            Some(entry) => {
+                entry.sources.insert(source);
+                entry.online_until = entry.online_until.max(until);
+                false
+            },
+            None => {
+                self.peers.insert(peer, PeerEntry {
+                    sources: BTreeSet::from([source]),
+                    online_until: until,
+                    rtt: None,
+                    consecutive_failures: 0,
+                    ping_failures: 0,
+                    presence: None,
+                    presence_requested: false,
+                    shares_last_seen: false,
+                });
+                true
+            },
+        }
+    }
+
+    // Takes away what the reason invalidates. Returns the entry once no source is left and the peer is gone
+    pub fn remove(&mut self, peer: &PeerId, reason: DepartureReason) -> Option<PeerEntry> {
+        let entry = self.peers.get_mut(peer)?;
+        entry.sources.retain(|source| !reason.withdraws(*source));
+        if !entry.sources.is_empty() {
+            return None;
+        }
+        self.peers.remove(peer)
+    }
+
+    pub fn clear(&mut self) {
+        self.peers.clear();
+    }
+
+    pub fn contains(&self, peer: &PeerId) -> bool {
+        self.peers.contains_key(peer)
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&PeerEntry> {
+        self.peers.get(peer)
+    }
+
+    // Takes away the expiring sources once `online_until` passed. Returns the peers left without any source,
+    // they stay listed until a presence lookup finds a newer record or removes them with `Timeout`
+    pub fn lapsed(&mut self, now: Instant) -> Vec<PeerId> {
+        let mut lapsed = Vec::new();
+        for (peer, entry) in self.peers.iter_mut() {
+            if entry.online_until > now {
+                continue;
+            }
+            entry.sources.retain(|source| !source.expires());
+            if entry.sources.is_empty() {
+                lapsed.push(*peer);
+            }
+        }
+        lapsed
+    }
+
+    // True if the status changed
+    pub fn set_presence(&mut self, peer: &PeerId, presence: Presence) -> bool {
+        match self.peers.get_mut(peer) {
+            Some(entry) if entry.presence.as_ref() != Some(&presence) => {
+                entry.presence = Some(presence);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn set_shares_last_seen(&mut self, peer: &PeerId, shares: bool) {
+        if let Some(entry) = self.peers.get_mut(peer) {
+            entry.shares_last_seen = shares;
+        }
+    }
+
+    // Online peers that share when they were last seen
+    pub fn sharing_last_seen(&self) -> Vec<PeerId> {
+        self.peers.iter().filter(|(_, entry)| entry.shows_last_seen()).map(|(peer, _)| *peer).collect()
+    }
+
+    // Online peers whose status is unknown and was not looked up yet. Marks them as looked up
+    pub fn presence_unknown(&mut self) -> Vec<PeerId> {
+        self.peers.iter_mut()
+            .filter(|(_, entry)| entry.presence.is_none() && !entry.presence_requested)
+            .map(|(peer, entry)| {
+                entry.presence_requested = true;
+                *peer
+            })
+            .collect()
+    }
+
+    pub fn record_rtt(&mut self, peer: &PeerId, rtt: Duration) -> Option<&PeerEntry> {
+        let entry = self.peers.get_mut(peer)?;
+        entry.rtt = Some(rtt);
+        entry.consecutive_failures = 0;
+        Some(entry)
+    }
+
+    pub fn record_ping_failure(&mut self, peer: &PeerId) -> Option<&PeerEntry> {
+        let entry = self.peers.get_mut(peer)?;
+        entry.consecutive_failures += 1;
+        entry.ping_failures += 1;
+        Some(entry)
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+use libp2p::{upnp, Multiaddr};
+
+// Progress of the UPnP port mapping, decides whether the relay circuit is needed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortMapping {
+    Disabled,
+    Searching,
+    Mapped(HashSet<Multiaddr>),
+    Failed,
+}
+
+impl PortMapping {
+    pub fn new(enabled: bool) -> Self {
+        if enabled {
+            PortMapping::Searching
+        } else {
+            PortMapping::Disabled
+        }
+    }
+
+    // Apply a UPnP event. Returns true if the relay requirement changed
+    pub fn on_event(&mut self, event: &upnp::Event) -> bool {
+        let needed_before = self.needs_relay();
+        match event {
+            upnp::Event::NewExternalAddr(addr) => {
+                if let PortMapping::Mapped(addrs) = self {
+                    addrs.insert(addr.clone());
+                } else {
+                    *self = PortMapping::Mapped(HashSet::from([addr.clone()]));
+                }
+            },
+            upnp::Event::ExpiredExternalAddr(addr) => {
+                if let PortMapping::Mapped(addrs) = self {
+                    addrs.remove(addr);
+                    if addrs.is_empty() {
+                        *self = PortMapping::Failed;
+                    }
+                }
+            },
+            upnp::Event::GatewayNotFound | upnp::Event::NonRoutableGateway => {
+                *self = PortMapping::Failed;
+            },
+        }
+        needed_before != self.needs_relay()
+    }
+
+    // Only a mapped port makes the relay unnecessary. A gateway that refuses the mapping
+    // reports nothing, so the search may never end
+    pub fn needs_relay(&self) -> bool {
+        !matches!(self, PortMapping::Mapped(_))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PortMapping::Disabled => "DISABLED",
+            PortMapping::Searching => "SEARCHING",
+            PortMapping::Mapped(_) => "MAPPED",
+            PortMapping::Failed => "FAILED",
+        }
+    }
+}
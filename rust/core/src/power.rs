@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+// How much work the node may do, set by the app from its lifecycle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PowerMode {
+    #[default]
+    Foreground,
+    // App not visible: slower discovery, no mDNS or Kademlia random walks
+    Background,
+    // Only the relay reservation is kept, so peers can still reach us through the circuit
+    Suspended,
+}
+
+impl PowerMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowerMode::Foreground => "FOREGROUND",
+            PowerMode::Background => "BACKGROUND",
+            PowerMode::Suspended => "SUSPENDED",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "FOREGROUND" => Some(PowerMode::Foreground),
+            "BACKGROUND" => Some(PowerMode::Background),
+            "SUSPENDED" => Some(PowerMode::Suspended),
+            _ => None,
+        }
+    }
+
+    // Discovery tick: address book, rendezvous renewal, presence lookups
+    pub fn discovery_interval(&self) -> Duration {
+        match self {
+            PowerMode::Foreground => Duration::from_secs(15),
+            PowerMode::Background => Duration::from_secs(60),
+            PowerMode::Suspended => Duration::from_secs(300),
+        }
+    }
+
+    // Ping handlers take the interval when a connection opens, so connections are reopened on a change
+    pub fn ping_interval(&self) -> Duration {
+        match self {
+            PowerMode::Foreground => Duration::from_secs(30),
+            PowerMode::Background => Duration::from_secs(60),
+            PowerMode::Suspended => Duration::from_secs(120),
+        }
+    }
+
+    // Gossipsub heartbeat: mesh upkeep, gossip and redials of explicit peers
+    pub fn heartbeat_interval(&self) -> Duration {
+        match self {
+            PowerMode::Foreground => Duration::from_secs(1),
+            PowerMode::Background => Duration::from_secs(10),
+            PowerMode::Suspended => Duration::from_secs(60),
+        }
+    }
+
+    // mDNS, Kademlia random walks, relay searches and hole punching retries
+    pub fn active_discovery(&self) -> bool {
+        *self == PowerMode::Foreground
+    }
+
+    // Connections to other peers and our presence record. Suspended keeps neither,
+    // so pings and gossip only run on the relay connection
+    pub fn keeps_peers(&self) -> bool {
+        *self != PowerMode::Suspended
+    }
+}
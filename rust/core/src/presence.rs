@@ -0,0 +1,157 @@
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use libp2p::{
+    core::SignedEnvelope,
+    identity::Keypair,
+    kad::Record,
+    PeerId,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::network::Network;
+
+// How long a presence record is valid. Peers whose record ran out and who are not connected are offline
+pub const PRESENCE_TTL: Duration = Duration::from_secs(120);
+// Republish well before expiry, so one lost put does not take us offline
+pub const PRESENCE_REPUBLISH: Duration = Duration::from_secs(50);
+
+// Longest status line, in characters
+pub const MAX_STATUS_TEXT: usize = 140;
+
+const DOMAIN: &str = "p2p-chat-presence";
+const PAYLOAD_TYPE: &[u8] = b"/p2p_msg/presence";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceStatus {
+    #[default]
+    Online,
+    Away,
+    DoNotDisturb,
+    // Still connected and receiving, but shown as offline to others
+    Invisible,
+}
+
+impl PresenceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PresenceStatus::Online => "ONLINE",
+            PresenceStatus::Away => "AWAY",
+            PresenceStatus::DoNotDisturb => "DND",
+            PresenceStatus::Invisible => "INVISIBLE",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "ONLINE" => Some(PresenceStatus::Online),
+            "AWAY" => Some(PresenceStatus::Away),
+            "DND" => Some(PresenceStatus::DoNotDisturb),
+            "INVISIBLE" => Some(PresenceStatus::Invisible),
+            _ => None,
+        }
+    }
+}
+
+// Status and status line published with our presence
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    pub status: PresenceStatus,
+    pub text: String,
+}
+
+impl Presence {
+    pub fn new(status: PresenceStatus, text: &str) -> Self {
+        Self { status, text: text.trim().chars().take(MAX_STATUS_TEXT).collect() }
+    }
+
+    // "<STATUS>:<text>", the text may contain ':'
+    pub fn parse(value: &str) -> Option<Self> {
+        let (status, text) = value.split_once(':').unwrap_or((value, ""));
+        Some(Self::new(PresenceStatus::parse(status)?, text))
+    }
+}
+
+impl fmt::Display for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.status.as_str(), self.text)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Payload {
+    issued_unix_ms: u64,
+    expires_unix_ms: u64,
+    presence: Presence,
+    // False if the peer asked others not to record when it was last seen
+    share_last_seen: bool,
+}
+
+// Contents of a presence record that passed verification
+#[derive(Debug, Clone)]
+pub struct VerifiedPresence {
+    pub peer: PeerId,
+    pub expires: Instant,
+    pub presence: Presence,
+    pub share_last_seen: bool,
+}
+
+fn unix_ms(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+// Our presence: a record under the presence key, signed with the identity key and bounded by PRESENCE_TTL
+pub fn presence_record(keypair: &Keypair, network: &Network, presence: &Presence, share_last_seen: bool) -> Record {
+    presence_record_with_ttl(keypair, network, presence, share_last_seen, PRESENCE_TTL)
+}
+
+// Same with another lifetime. Records living longer than PRESENCE_TTL do not verify
+pub fn presence_record_with_ttl(keypair: &Keypair, network: &Network, presence: &Presence, share_last_seen: bool, ttl: Duration) -> Record {
+    let peer = PeerId::from(keypair.public());
+    let now = SystemTime::now();
+    let payload = Payload {
+        issued_unix_ms: unix_ms(now),
+        expires_unix_ms: unix_ms(now + ttl),
+        presence: presence.clone(),
+        share_last_seen,
+    };
+    let envelope = SignedEnvelope::new(
+        keypair,
+        DOMAIN.to_string(),
+        PAYLOAD_TYPE.to_vec(),
+        bincode::serialize(&payload).expect("Presence serialization"),
+    ).expect("Identity key can sign");
+
+    let mut record = Record::new(network.presence_key(&peer), envelope.into_protobuf_encoding());
+    record.publisher = Some(peer);
+    record.expires = Some(Instant::now() + ttl);
+    record
+}
+
+// Contents of a presence record. None if it is not one, is forged, or has expired.
+// The signature must come from the peer named in the key, so nobody can keep someone else online
+pub fn verify_presence(network: &Network, record: &Record) -> Option<VerifiedPresence> {
+    let envelope = SignedEnvelope::from_protobuf_encoding(&record.value).ok()?;
+    let (payload, key) = envelope.payload_and_signing_key(DOMAIN.to_string(), PAYLOAD_TYPE).ok()?;
+    let peer = key.to_peer_id();
+    if record.key != network.presence_key(&peer) {
+        return None;
+    }
+    let payload: Payload = bincode::deserialize(payload).ok()?;
+    let expires = UNIX_EPOCH + Duration::from_millis(payload.expires_unix_ms);
+    // A record claiming a longer life than we hand out is not ours to trust
+    if payload.expires_unix_ms.saturating_sub(payload.issued_unix_ms) > PRESENCE_TTL.as_millis() as u64 {
+        return None;
+    }
+    let remaining = expires.duration_since(SystemTime::now()).ok()?;
+    Some(VerifiedPresence {
+        peer,
+        expires: Instant::now() + remaining,
+        presence: Presence::new(payload.presence.status, &payload.presence.text),
+        share_last_seen: payload.share_last_seen,
+    })
+}
+
+// Store filter for inbound records: presence records must verify, anything else is kept as before
+pub fn accept_record(network: &Network, record: &Record) -> bool {
+    !network.is_presence_key(&record.key) || verify_presence(network, record).is_some()
+}
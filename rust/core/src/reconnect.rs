@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use libp2p::{Multiaddr, PeerId};
+use rand::Rng;
+
+// Exponential backoff with jitter
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max, attempt: 0 }
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    // Next delay: initial * 2^attempt (capped), randomized between 50% and 100%
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempt.min(16));
+        let base = self.initial.saturating_mul(factor).min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let jitter = rand::thread_rng().gen_range(0.5..=1.0);
+        base.mul_f64(jitter)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1), Duration::from_secs(120))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    Relay,
+    Bootstrap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    Connecting,
+    Connected,
+    Waiting(Instant),
+}
+
+#[derive(Debug)]
+struct Target {
+    kind: TargetKind,
+    addr: Multiaddr,
+    state: LinkState,
+    backoff: Backoff,
+}
+
+// Overall connectivity reported to the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Connecting,
+    Connected,
+    Reconnecting,
+}
+
+impl Connectivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Connectivity::Connecting => "CONNECTING",
+            Connectivity::Connected => "CONNECTED",
+            Connectivity::Reconnecting => "RECONNECTING",
+        }
+    }
+}
+
+// Keeps relay and bootstrap peers connected, redialing with backoff when they drop
+#[derive(Debug, Default)]
+pub struct ReconnectSupervisor {
+    targets: HashMap<PeerId, Target>,
+}
+
+impl ReconnectSupervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Register a peer to supervise. `addr` is the full dialable address (including /p2p/<id>)
+    pub fn watch(&mut self, peer_id: PeerId, addr: Multiaddr, kind: TargetKind) {
+        self.targets.entry(peer_id)
+            .and_modify(|t| {
+                // The relay role wins if a peer is both relay and bootstrap
+                if kind == TargetKind::Relay {
+                    t.kind = kind;
+                }
+            })
+            .or_insert(Target {
+                kind,
+                addr,
+                state: LinkState::Connecting,
+                backoff: Backoff::default(),
+            });
+    }
+
+    pub fn is_watched(&self, peer_id: &PeerId) -> bool {
+        self.targets.contains_key(peer_id)
+    }
+
+    pub fn kind(&self, peer_id: &PeerId) -> Option<TargetKind> {
+        self.targets.get(peer_id).map(|t| t.kind)
+    }
+
+    pub fn on_connected(&mut self, peer_id: &PeerId) {
+        if let Some(target) = self.targets.get_mut(peer_id) {
+            target.state = LinkState::Connected;
+            target.backoff.reset();
+        }
+    }
+
+    // Connection lost or dial failed: schedule the next attempt. Returns the delay used
+    pub fn on_disconnected(&mut self, peer_id: &PeerId) -> Option<Duration> {
+        let target = self.targets.get_mut(peer_id)?;
+        if let LinkState::Waiting(_) = target.state {
+            return None;
+        }
+        let delay = target.backoff.next_delay();
+        target.state = LinkState::Waiting(Instant::now() + delay);
+        Some(delay)
+    }
+
+    // Targets whose backoff expired. They are moved to `Connecting`
+    pub fn due(&mut self, now: Instant) -> Vec<(PeerId, Multiaddr, u32)> {
+        let mut due = Vec::new();
+        for (peer_id, target) in self.targets.iter_mut() {
+            if let LinkState::Waiting(at) = target.state {
+                if at <= now {
+                    target.state = LinkState::Connecting;
+                    due.push((*peer_id, target.addr.clone(), target.backoff.attempt()));
+                }
+            }
+        }
+        due
+    }
+
+    // Force every disconnected target to be retried right away
+    pub fn retry_all_now(&mut self) {
+        let now = Instant::now();
+        for target in self.targets.values_mut() {
+            if let LinkState::Waiting(_) = target.state {
+                target.state = LinkState::Waiting(now);
+                target.backoff.reset();
+            }
+        }
+    }
+
+    pub fn connectivity(&self) -> Connectivity {
+        // Relays decide when there are any, otherwise any bootstrap peer is enough
+        let has_relay = self.targets.values().any(|t| t.kind == TargetKind::Relay);
+        let relevant: Vec<&Target> = self.targets.values()
+            .filter(|t| !has_relay || t.kind == TargetKind::Relay)
+            .collect();
+
+        if relevant.iter().any(|t| t.state == LinkState::Connected) {
+            Connectivity::Connected
+        } else if relevant.iter().all(|t| t.state == LinkState::Connecting && t.backoff.attempt() == 0) {
+            Connectivity::Connecting
+        } else {
+            Connectivity::Reconnecting
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use libp2p::{
+    kad::{
+        store::{self, MemoryStore, MemoryStoreConfig, RecordStore},
+        ProviderRecord, Record, RecordKey,
+    },
+    Multiaddr, PeerId,
+};
+use redb::{Database, Durability, ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+const RECORDS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("records");
+// Keyed by <u16 key length><key><provider peer id>, so all providers of a key share a prefix
+const PROVIDERS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("providers");
+
+// How often the owner should call `save_if_dirty`. The DHT republishes anything lost in between
+pub const DHT_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+// redb::Error is large, boxed once so the results passed around stay small
+#[derive(Debug)]
+struct StoreError(Box<redb::Error>);
+
+impl<E: Into<redb::Error>> From<E> for StoreError {
+    fn from(e: E) -> Self {
+        Self(Box::new(e.into()))
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// Size and lifetime limits of the DHT record store
+#[derive(Debug, Clone)]
+pub struct DhtStoreConfig {
+    pub max_records: usize,
+    pub max_value_bytes: usize,
+    pub max_providers_per_key: usize,
+    pub max_provided_keys: usize,
+    // Upper bound for record and provider lifetimes, also applied to records without expiry
+    pub max_ttl: Duration,
+}
+
+impl Default for DhtStoreConfig {
+    fn default() -> Self {
+        let memory = MemoryStoreConfig::default();
+        Self {
+            max_records: memory.max_records,
+            max_value_bytes: memory.max_value_bytes,
+            max_providers_per_key: memory.max_providers_per_key,
+            max_provided_keys: memory.max_provided_keys,
+            max_ttl: Duration::from_secs(48 * 3600),
+        }
+    }
+}
+
+// On-disk form, expiry as wall-clock time so it survives restarts
+#[derive(Serialize, Deserialize)]
+struct StoredRecord {
+    value: Vec<u8>,
+    publisher: Option<Vec<u8>>,
+    expires_unix_ms: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredProvider {
+    addresses: Vec<Vec<u8>>,
+    expires_unix_ms: u64,
+}
+
+fn unix_ms(at: Instant) -> u64 {
+    let wall = SystemTime::now() + at.saturating_duration_since(Instant::now());
+    wall.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+// None if already expired
+fn instant(unix_ms: u64) -> Option<Instant> {
+    let wall = UNIX_EPOCH + Duration::from_millis(unix_ms);
+    let remaining = wall.duration_since(SystemTime::now()).ok()?;
+    Some(Instant::now() + remaining)
+}
+
+fn provider_row(key: &RecordKey, provider: &PeerId) -> Vec<u8> {
+    let mut row = provider_prefix(key);
+    row.extend_from_slice(&provider.to_bytes());
+    row
+}
+
+fn provider_prefix(key: &RecordKey) -> Vec<u8> {
+    let key = key.as_ref();
+    let mut prefix = (key.len() as u16).to_be_bytes().to_vec();
+    prefix.extend_from_slice(key);
+    prefix
+}
+
+// Kademlia record store, in memory or backed by a redb file.
+// The in-memory store stays the source of truth for lookups, the file only restores it on startup.
+// Changes are collected and written in one transaction by `save_if_dirty`, not on every put
+pub struct DhtStore {
+    memory: MemoryStore,
+    db: Option<Database>,
+    max_ttl: Duration,
+    // Keys whose record or providers changed since the last save
+    dirty_records: HashSet<RecordKey>,
+    dirty_providers: HashSet<RecordKey>,
+}
+
+impl DhtStore {
+    pub fn in_memory(local_id: PeerId, config: DhtStoreConfig) -> Self {
+        Self {
+            memory: MemoryStore::with_config(local_id, Self::memory_config(&config)),
+            db: None,
+            max_ttl: config.max_ttl,
+            dirty_records: HashSet::new(),
+            dirty_providers: HashSet::new(),
+        }
+    }
+
+    // Falls back to memory only if the file cannot be opened, the DHT keeps working either way
+    pub fn persistent(local_id: PeerId, path: impl AsRef<Path>, config: DhtStoreConfig) -> Self {
+        let mut dht_store = Self::in_memory(local_id, config);
+        let path = path.as_ref();
+        match Database::create(path) {
+            Ok(db) => {
+                dht_store.db = Some(db);
+                match dht_store.load() {
+                    Ok((records, providers)) => info!("[DHT] Restored {} records and {} provider records from {:?}", records, providers, path),
+                    Err(e) => warn!("[DHT] Failed to restore records from {:?}: {}", path, e),
+                }
+            },
+            Err(e) => warn!("[DHT] Cannot open record store {:?}, keeping records in memory: {}", path, e),
+        }
+        dht_store
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.db.is_some()
+    }
+
+    fn memory_config(config: &DhtStoreConfig) -> MemoryStoreConfig {
+        MemoryStoreConfig {
+            max_records: config.max_records,
+            max_value_bytes: config.max_value_bytes,
+            max_providers_per_key: config.max_providers_per_key,
+            max_provided_keys: config.max_provided_keys,
+        }
+    }
+
+    // Caps the lifetime, records without expiry get the maximum
+    fn clamp_expiry(&self, expires: Option<Instant>) -> Instant {
+        let latest = Instant::now() + self.max_ttl;
+        expires.map_or(latest, |e| e.min(latest))
+    }
+
+    // Writes the records and providers changed since the last call. Failures are logged,
+    // the in-memory store has the changes either way
+    pub fn save_if_dirty(&mut self) {
+        if self.dirty_records.is_empty() && self.dirty_providers.is_empty() {
+            return;
+        }
+        let records = std::mem::take(&mut self.dirty_records);
+        let providers = std::mem::take(&mut self.dirty_providers);
+        if let Err(e) = self.write(&records, &providers) {
+            warn!("[DHT] Failed to persist {} records and {} provider keys: {}", records.len(), providers.len(), e);
+        }
+    }
+
+    // Reads every row into memory, deleting expired and unreadable ones
+    fn load(&mut self) -> Result<(usize, usize), StoreError> {
+        let Some(db) = &self.db else {
+            return Ok((0, 0));
+        };
+        let mut records = Vec::new();
+        let mut providers = Vec::new();
+        let mut stale_records = Vec::new();
+        let mut stale_providers = Vec::new();
+
+        let tx = db.begin_write()?;
+        {
+            let table = tx.open_table(RECORDS)?;
+            for row in table.iter()? {
+                let (key, value) = row?;
+                let key = key.value().to_vec();
+                let parsed = bincode::deserialize::<StoredRecord>(value.value()).ok()
+                    .and_then(|stored| Some((instant(stored.expires_unix_ms)?, stored)));
+                match parsed {
+                    Some((expires, stored)) => records.push(Record {
+                        key: RecordKey::from(key),
+                        value: stored.value,
+                        publisher: stored.publisher.and_then(|p| PeerId::from_bytes(&p).ok()),
+                        expires: Some(expires),
+                    }),
+                    None => stale_records.push(key),
+                }
+            }
+
+            let table = tx.open_table(PROVIDERS)?;
+            for row in table.iter()? {
+                let (row_key, value) = row?;
+                let row_key = row_key.value().to_vec();
+                let parsed = (|| {
+                    let key_len = u16::from_be_bytes(row_key.get(..2)?.try_into().ok()?) as usize;
+                    let key = row_key.get(2..2 + key_len)?.to_vec();
+                    let provider = PeerId::from_bytes(row_key.get(2 + key_len..)?).ok()?;
+                    let stored = bincode::deserialize::<StoredProvider>(value.value()).ok()?;
+                    Some(ProviderRecord {
+                        key: RecordKey::from(key),
+                        provider,
+                        expires: Some(instant(stored.expires_unix_ms)?),
+                        addresses: stored.addresses.into_iter().filter_map(|a| Multiaddr::try_from(a).ok()).collect(),
+                    })
+                })();
+                match parsed {
+                    Some(record) => providers.push(record),
+                    None => stale_providers.push(row_key),
+                }
+            }
+        }
+        {
+            let mut table = tx.open_table(RECORDS)?;
+            for key in &stale_records {
+                table.remove(key.as_slice())?;
+            }
+            let mut table = tx.open_table(PROVIDERS)?;
+            for key in &stale_providers {
+                table.remove(key.as_slice())?;
+            }
+        }
+        tx.commit()?;
+
+        let counts = (records.len(), providers.len());
+        for record in records {
+            let _ = self.memory.put(record);
+        }
+        for record in providers {
+            let _ = self.memory.add_provider(record);
+        }
+        Ok(counts)
+    }
+
+    // Mirrors the in-memory state of the given keys into the file, in one transaction
+    fn write(&self, records: &HashSet<RecordKey>, providers: &HashSet<RecordKey>) -> Result<(), StoreError> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
+        let mut tx = db.begin_write()?;
+        // Crash-safe without an fsync per save, the DHT republishes anything lost
+        tx.set_durability(Durability::Eventual);
+        {
+            let mut table = tx.open_table(RECORDS)?;
+            for key in records {
+                match self.memory.get(key) {
+                    Some(record) => {
+                        let stored = StoredRecord {
+                            value: record.value.clone(),
+                            publisher: record.publisher.map(|p| p.to_bytes()),
+                            expires_unix_ms: record.expires.map(unix_ms).unwrap_or(0),
+                        };
+                        let value = bincode::serialize(&stored).expect("Record serialization");
+                        table.insert(key.as_ref(), value.as_slice())?;
+                    },
+                    None => {
+                        table.remove(key.as_ref())?;
+                    },
+                }
+            }
+
+            // Providers of a key are rewritten from memory, which already dropped the farthest ones
+            let mut table = tx.open_table(PROVIDERS)?;
+            for key in providers {
+                let prefix = provider_prefix(key);
+                let existing: Vec<Vec<u8>> = table.range(prefix.as_slice()..)?
+                    .map_while(|row| row.ok().map(|(k, _)| k.value().to_vec()))
+                    .take_while(|k| k.starts_with(&prefix))
+                    .collect();
+                for row in existing {
+                    table.remove(row.as_slice())?;
+                }
+                for record in self.memory.providers(key) {
+                    let stored = StoredProvider {
+                        addresses: record.addresses.iter().map(|a| a.to_vec()).collect(),
+                        expires_unix_ms: unix_ms(self.clamp_expiry(record.expires)),
+                    };
+                    let value = bincode::serialize(&stored).expect("Provider record serialization");
+                    table.insert(provider_row(key, &record.provider).as_slice(), value.as_slice())?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+impl Drop for DhtStore {
+    fn drop(&mut self) {
+        self.save_if_dirty();
+    }
+}
+
+impl RecordStore for DhtStore {
+    type RecordsIter<'a> = <MemoryStore as RecordStore>::RecordsIter<'a>;
+    type ProvidedIter<'a> = <MemoryStore as RecordStore>::ProvidedIter<'a>;
+
+    fn get(&self, k: &RecordKey) -> Option<Cow<'_, Record>> {
+        self.memory.get(k)
+    }
+
+    fn put(&mut self, mut r: Record) -> store::Result<()> {
+        r.expires = Some(self.clamp_expiry(r.expires));
+        let key = r.key.clone();
+        self.memory.put(r)?;
+        if self.db.is_some() {
+            self.dirty_records.insert(key);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, k: &RecordKey) {
+        self.memory.remove(k);
+        if self.db.is_some() {
+            self.dirty_records.insert(k.clone());
+        }
+    }
+
+    fn records(&self) -> Self::RecordsIter<'_> {
+        self.memory.records()
+    }
+
+    fn add_provider(&mut self, mut record: ProviderRecord) -> store::Result<()> {
+        record.expires = Some(self.clamp_expiry(record.expires));
+        let key = record.key.clone();
+        self.memory.add_provider(record)?;
+        if self.db.is_some() {
+            self.dirty_providers.insert(key);
+        }
+        Ok(())
+    }
+
+    fn providers(&self, key: &RecordKey) -> Vec<ProviderRecord> {
+        self.memory.providers(key)
+    }
+
+    fn provided(&self) -> Self::ProvidedIter<'_> {
+        self.memory.provided()
+    }
+
+    fn remove_provider(&mut self, k: &RecordKey, p: &PeerId) {
+        self.memory.remove_provider(k, p);
+        if self.db.is_some() {
+            self.dirty_providers.insert(k.clone());
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use libp2p::{Multiaddr, PeerId};
+
+// Maximum number of discovered relays a node reserves on
+pub const MAX_DISCOVERED_RELAYS: usize = 2;
+
+// Time to wait for RTT measurements before picking relays
+const SELECTION_WINDOW: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Default)]
+struct Candidate {
+    addr: Option<Multiaddr>,
+    rtt: Option<Duration>,
+    failed: bool,
+}
+
+// Relays found in the DHT, ranked by ping RTT
+#[derive(Debug, Default)]
+pub struct RelayCandidates {
+    candidates: HashMap<PeerId, Candidate>,
+    first_seen: Option<Instant>,
+}
+
+impl RelayCandidates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns true if the peer was not known yet
+    pub fn add(&mut self, peer_id: PeerId) -> bool {
+        if self.candidates.contains_key(&peer_id) {
+            return false;
+        }
+        self.first_seen.get_or_insert_with(Instant::now);
+        self.candidates.insert(peer_id, Candidate::default());
+        true
+    }
+
+    pub fn contains(&self, peer_id: &PeerId) -> bool {
+        self.candidates.contains_key(peer_id)
+    }
+
+    // Physical address the candidate was reached on
+    pub fn set_address(&mut self, peer_id: &PeerId, addr: Multiaddr) {
+        if let Some(candidate) = self.candidates.get_mut(peer_id) {
+            candidate.addr = Some(addr);
+        }
+    }
+
+    pub fn record_rtt(&mut self, peer_id: &PeerId, rtt: Duration) {
+        if let Some(candidate) = self.candidates.get_mut(peer_id) {
+            candidate.rtt = Some(rtt);
+            candidate.failed = false;
+        }
+    }
+
+    pub fn record_failure(&mut self, peer_id: &PeerId) {
+        if let Some(candidate) = self.candidates.get_mut(peer_id) {
+            candidate.failed = true;
+        }
+    }
+
+    // Best `count` relays by RTT, excluding `exclude`. Waits until every candidate
+    // answered or the selection window elapsed
+    pub fn select(&self, count: usize, exclude: &[PeerId]) -> Vec<(PeerId, Multiaddr)> {
+        let Some(first_seen) = self.first_seen else {
+            return Vec::new();
+        };
+
+        let pending = self.candidates.values().any(|c| c.rtt.is_none() && !c.failed);
+        if pending && first_seen.elapsed() < SELECTION_WINDOW {
+            return Vec::new();
+        }
+
+        let mut ranked: Vec<(PeerId, Multiaddr, Duration)> = self.candidates.iter()
+            .filter(|(id, c)| !c.failed && !exclude.contains(id))
+            .filter_map(|(id, c)| Some((*id, c.addr.clone()?, c.rtt?)))
+            .collect();
+        ranked.sort_by_key(|(_, _, rtt)| *rtt);
+
+        ranked.into_iter()
+            .take(count)
+            .map(|(id, addr, _)| (id, addr))
+            .collect()
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use libp2p::{
+    rendezvous::{Cookie, Namespace, Ttl},
+    PeerId,
+};
+
+// Lifetime of our registrations. Renewed at half-life, so a node that goes away
+// disappears from discovery within a few minutes instead of the 2h rendezvous default
+pub const REGISTRATION_TTL: Ttl = 300;
+// Shortest TTL the relay accepts
+pub const MIN_REGISTRATION_TTL: Ttl = 60;
+// Registrations fetched per discover request
+pub const DISCOVER_LIMIT: u64 = 100;
+// Wait before retrying a failed registration
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// Namespaces we are registered under and what each rendezvous point already told us.
+// Pure bookkeeping, the caller talks to the rendezvous client
+pub struct RendezvousState {
+    global: Namespace,
+    // Room namespace -> room name
+    rooms: HashMap<Namespace, String>,
+    // (rendezvous point, namespace) -> when to register again
+    renew_at: HashMap<(PeerId, Namespace), Instant>,
+    // (rendezvous point, namespace) -> cookie for incremental discovery
+    cookies: HashMap<(PeerId, Namespace), Cookie>,
+    // Room namespace -> peers already reported as members
+    members: HashMap<Namespace, HashSet<PeerId>>,
+}
+
+impl RendezvousState {
+    pub fn new(global: Namespace) -> Self {
+        Self {
+            global,
+            rooms: HashMap::new(),
+            renew_at: HashMap::new(),
+            cookies: HashMap::new(),
+            members: HashMap::new(),
+        }
+    }
+
+    pub fn join_room(&mut self, namespace: Namespace, room: String) -> bool {
+        self.rooms.insert(namespace, room).is_none()
+    }
+
+    // Returns the namespace to unregister from, if the room was joined
+    pub fn leave_room(&mut self, room: &str) -> Option<Namespace> {
+        let namespace = self.rooms.iter().find(|(_, r)| *r == room).map(|(ns, _)| ns.clone())?;
+        self.rooms.remove(&namespace);
+        self.renew_at.retain(|(_, ns), _| *ns != namespace);
+        self.cookies.retain(|(_, ns), _| *ns != namespace);
+        self.members.remove(&namespace);
+        Some(namespace)
+    }
+
+    // Room name of a namespace, None for the global one
+    pub fn room(&self, namespace: &Namespace) -> Option<&str> {
+        self.rooms.get(namespace).map(String::as_str)
+    }
+
+    // True the first time a peer shows up in a joined room
+    pub fn add_member(&mut self, namespace: &Namespace, peer: PeerId) -> bool {
+        self.rooms.contains_key(namespace) && self.members.entry(namespace.clone()).or_default().insert(peer)
+    }
+
+    pub fn namespaces(&self) -> Vec<Namespace> {
+        std::iter::once(self.global.clone()).chain(self.rooms.keys().cloned()).collect()
+    }
+
+    // Namespaces to (re-)register at this rendezvous point now. They count as in flight until
+    // on_registered / on_register_failed
+    pub fn due_registrations(&mut self, point: PeerId, now: Instant) -> Vec<Namespace> {
+        let due: Vec<Namespace> = self.namespaces()
+            .into_iter()
+            .filter(|ns| self.renew_at.get(&(point, ns.clone())).is_none_or(|at| *at <= now))
+            .collect();
+        for ns in &due {
+            self.renew_at.insert((point, ns.clone()), now + RETRY_DELAY);
+        }
+        due
+    }
+
+    pub fn on_registered(&mut self, point: PeerId, namespace: Namespace, ttl: Ttl) {
+        let renew_in = Duration::from_secs(ttl / 2).max(RETRY_DELAY);
+        self.renew_at.insert((point, namespace), Instant::now() + renew_in);
+    }
+
+    pub fn on_register_failed(&mut self, point: PeerId, namespace: Namespace) {
+        self.renew_at.insert((point, namespace), Instant::now() + RETRY_DELAY);
+    }
+
+    pub fn cookie(&self, point: PeerId, namespace: &Namespace) -> Option<Cookie> {
+        self.cookies.get(&(point, namespace.clone())).cloned()
+    }
+
+    pub fn set_cookie(&mut self, point: PeerId, cookie: Cookie) {
+        if let Some(namespace) = cookie.namespace().cloned() {
+            self.cookies.insert((point, namespace), cookie);
+        }
+    }
+
+    // The rendezvous point went away: register and discover from scratch next time
+    pub fn forget_point(&mut self, point: &PeerId) {
+        self.renew_at.retain(|(p, _), _| p != point);
+        self.cookies.retain(|(p, _), _| p != point);
+    }
+}
@@ -0,0 +1,214 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use libp2p::{
+    core::transport::{ListenerId, TransportError, TransportEvent},
+    futures::future::{self, BoxFuture, FutureExt},
+    multiaddr::Protocol,
+    tcp::tokio::TcpStream,
+    Multiaddr, Transport,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// SOCKS5 proxy for outbound connections, e.g. a local Tor client on 127.0.0.1:9050
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    pub addr: SocketAddr,
+    // RFC 1929 username/password. Tor uses them to isolate circuits
+    pub credentials: Option<(String, String)>,
+}
+
+impl Socks5Proxy {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self { addr, credentials: None }
+    }
+
+    // "host:port" or "user:password@host:port"
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim();
+        let (credentials, host) = match value.rsplit_once('@') {
+            Some((auth, host)) => {
+                let (user, password) = auth.split_once(':')
+                    .ok_or_else(|| format!("Invalid SOCKS5 credentials in '{}', expected user:password", value))?;
+                (Some((user.to_string(), password.to_string())), host)
+            },
+            None => (None, value),
+        };
+
+        let addr = host.to_socket_addrs()
+            .map_err(|e| format!("Invalid SOCKS5 proxy address '{}': {}", host, e))?
+            .next()
+            .ok_or_else(|| format!("SOCKS5 proxy address '{}' did not resolve", host))?;
+        Ok(Self { addr, credentials })
+    }
+}
+
+// Destination as sent to the proxy. Host names are resolved by the proxy, never locally
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Destination {
+    Ip(IpAddr),
+    Domain(String),
+}
+
+// `/ip4|ip6|dns|dns4|dns6/<host>/tcp/<port>[/p2p/<id>]`, anything else is not ours
+fn destination(addr: &Multiaddr) -> Option<(Destination, u16)> {
+    let mut iter = addr.iter();
+    let host = match iter.next()? {
+        Protocol::Ip4(ip) => Destination::Ip(ip.into()),
+        Protocol::Ip6(ip) => Destination::Ip(ip.into()),
+        Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) => Destination::Domain(name.to_string()),
+        _ => return None,
+    };
+    let Protocol::Tcp(port) = iter.next()? else {
+        return None;
+    };
+    match iter.next() {
+        None | Some(Protocol::P2p(_)) if iter.next().is_none() => Some((host, port)),
+        _ => None,
+    }
+}
+
+fn reply_error(code: u8) -> io::Error {
+    let (kind, reason) = match code {
+        1 => (io::ErrorKind::Other, "general SOCKS server failure"),
+        2 => (io::ErrorKind::PermissionDenied, "connection not allowed by ruleset"),
+        3 => (io::ErrorKind::Other, "network unreachable"),
+        4 => (io::ErrorKind::Other, "host unreachable"),
+        5 => (io::ErrorKind::ConnectionRefused, "connection refused"),
+        6 => (io::ErrorKind::TimedOut, "TTL expired"),
+        7 => (io::ErrorKind::Unsupported, "command not supported"),
+        8 => (io::ErrorKind::Unsupported, "address type not supported"),
+        _ => (io::ErrorKind::Other, "unknown error"),
+    };
+    io::Error::new(kind, format!("SOCKS5 proxy: {} ({})", reason, code))
+}
+
+fn protocol_error(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("SOCKS5 proxy: {}", reason))
+}
+
+// RFC 1928 CONNECT, optionally authenticated with RFC 1929
+async fn connect(proxy: Socks5Proxy, destination: Destination, port: u16) -> io::Result<TcpStream> {
+    let mut stream = tokio::net::TcpStream::connect(proxy.addr).await?;
+
+    // Greeting: offer "no authentication", or only username/password when configured
+    let method = if proxy.credentials.is_some() { 2 } else { 0 };
+    stream.write_all(&[5, 1, method]).await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice[0] != 5 {
+        return Err(protocol_error("not a SOCKS5 server"));
+    }
+    if choice[1] != method {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "SOCKS5 proxy: no acceptable authentication method"));
+    }
+
+    if let Some((user, password)) = &proxy.credentials {
+        if user.len() > 255 || password.len() > 255 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "SOCKS5 credentials too long"));
+        }
+        let mut auth = vec![1, user.len() as u8];
+        auth.extend_from_slice(user.as_bytes());
+        auth.push(password.len() as u8);
+        auth.extend_from_slice(password.as_bytes());
+        stream.write_all(&auth).await?;
+
+        let mut status = [0u8; 2];
+        stream.read_exact(&mut status).await?;
+        if status[1] != 0 {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "SOCKS5 proxy: authentication failed"));
+        }
+    }
+
+    // CONNECT request
+    let mut request = vec![5, 1, 0];
+    match &destination {
+        Destination::Ip(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        },
+        Destination::Ip(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        },
+        Destination::Domain(name) => {
+            if name.len() > 255 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "SOCKS5 host name too long"));
+            }
+            request.push(3);
+            request.push(name.len() as u8);
+            request.extend_from_slice(name.as_bytes());
+        },
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    // Reply: VER REP RSV ATYP BND.ADDR BND.PORT
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[0] != 5 {
+        return Err(protocol_error("invalid reply"));
+    }
+    if head[1] != 0 {
+        return Err(reply_error(head[1]));
+    }
+    let bound_len = match head[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        _ => return Err(protocol_error("invalid bound address type")),
+    };
+    let mut bound = vec![0u8; bound_len + 2];
+    stream.read_exact(&mut bound).await?;
+
+    Ok(TcpStream(stream))
+}
+
+// Dial-only TCP transport tunnelling every connection through a SOCKS5 proxy.
+// It never listens and cannot hole-punch, which is what keeps the local address private
+#[derive(Debug, Clone)]
+pub struct Socks5Transport {
+    proxy: Socks5Proxy,
+}
+
+impl Socks5Transport {
+    pub fn new(proxy: Socks5Proxy) -> Self {
+        Self { proxy }
+    }
+}
+
+impl Transport for Socks5Transport {
+    type Output = TcpStream;
+    type Error = io::Error;
+    type ListenerUpgrade = future::Pending<Result<Self::Output, Self::Error>>;
+    type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+    fn listen_on(&mut self, _id: ListenerId, addr: Multiaddr) -> Result<(), TransportError<Self::Error>> {
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
+    fn remove_listener(&mut self, _id: ListenerId) -> bool {
+        false
+    }
+
+    fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        let Some((destination, port)) = destination(&addr) else {
+            return Err(TransportError::MultiaddrNotSupported(addr));
+        };
+        tracing::debug!("Dialing {} through SOCKS5 proxy {}", addr, self.proxy.addr);
+        Ok(connect(self.proxy.clone(), destination, port).boxed())
+    }
+
+    fn dial_as_listener(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+        Err(TransportError::MultiaddrNotSupported(addr))
+    }
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+        Poll::Pending
+    }
+
+    fn address_translation(&self, _listen: &Multiaddr, _observed: &Multiaddr) -> Option<Multiaddr> {
+        None
+    }
+}
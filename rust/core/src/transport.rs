@@ -1,48 +1,343 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use libp2p::{
-    core::{muxing::StreamMuxerBox, transport::Boxed},
-    futures::future::Either,
-    identity, noise, relay, tcp, yamux, PeerId, Transport,
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{timeout::{TransportTimeout, TransportTimeoutError}, Boxed},
+    },
+    futures::{future::Either, AsyncRead, AsyncWrite},
+    pnet::{PnetConfig, PreSharedKey},
+    dns, identity, noise, relay, tcp, websocket, yamux, PeerId, Transport,
 };
+use crate::socks::{Socks5Proxy, Socks5Transport};
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer};
+use tracing::{info, warn};
 
-// Build hybrid transport (TCP + Relay)
+// Security and multiplexer negotiation of a new connection must finish within this
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Optional transport settings. The defaults dial everything and listen on plain /ws only
+#[derive(Debug, Clone, Default)]
+pub struct TransportConfig {
+    // TLS server config for /wss listeners, only set once the certificate and key loaded
+    pub wss_tls: Option<websocket::tls::Config>,
+    // Enables WebRTC-direct (browser peers) when set, see identity::get_or_create_webrtc_certificate
+    pub webrtc_certificate: Option<libp2p_webrtc::tokio::Certificate>,
+    // Private network: only peers with the same key can connect. QUIC and WebRTC cannot carry
+    // the pnet handshake, so they are disabled while a key is set
+    pub pre_shared_key: Option<PreSharedKey>,
+    // Outbound TCP and WebSocket dials go through this SOCKS5 proxy (e.g. Tor)
+    pub socks5_proxy: Option<Socks5Proxy>,
+    // Proxy-only: no listeners and no direct dials at all, only the proxy and relay circuits
+    pub proxy_only: bool,
+    // Resolvers used where the OS DNS configuration is unreadable
+    pub dns_fallback: DnsFallback,
+}
+
+// Public resolvers to fall back on where the OS configuration is unreadable (Android has no
+// /etc/resolv.conf). `None` leaves /dns addresses unresolvable there
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DnsFallback {
+    #[default]
+    Cloudflare,
+    Google,
+    Quad9,
+    None,
+}
+
+impl DnsFallback {
+    // "cloudflare", "google", "quad9" or "none", empty for the default
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" => Ok(Self::default()),
+            "cloudflare" => Ok(Self::Cloudflare),
+            "google" => Ok(Self::Google),
+            "quad9" => Ok(Self::Quad9),
+            "none" => Ok(Self::None),
+            other => Err(format!("Unknown DNS fallback '{}' (cloudflare, google, quad9 or none)", other)),
+        }
+    }
+
+    fn resolver_config(&self) -> dns::ResolverConfig {
+        match self {
+            DnsFallback::Cloudflare => dns::ResolverConfig::cloudflare(),
+            DnsFallback::Google => dns::ResolverConfig::google(),
+            DnsFallback::Quad9 => dns::ResolverConfig::quad9(),
+            DnsFallback::None => dns::ResolverConfig::new(),
+        }
+    }
+}
+
+impl fmt::Display for DnsFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DnsFallback::Cloudflare => "cloudflare",
+            DnsFallback::Google => "google",
+            DnsFallback::Quad9 => "quad9",
+            DnsFallback::None => "none",
+        })
+    }
+}
+
+impl TransportConfig {
+    // Relay style layout: <dir>/cert.pem and <dir>/key.pem, used only if both exist and load
+    pub fn with_wss_certs_in(mut self, dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+        if cert.exists() && key.exists() {
+            self.wss_tls = wss_server_tls(&cert, &key);
+        }
+        self
+    }
+
+    pub fn with_webrtc(mut self, certificate: libp2p_webrtc::tokio::Certificate) -> Self {
+        self.webrtc_certificate = Some(certificate);
+        self
+    }
+
+    pub fn with_pre_shared_key(mut self, key: Option<PreSharedKey>) -> Self {
+        self.pre_shared_key = key;
+        self
+    }
+
+    pub fn with_dns_fallback(mut self, fallback: DnsFallback) -> Self {
+        self.dns_fallback = fallback;
+        self
+    }
+
+    pub fn with_socks5_proxy(mut self, proxy: Option<Socks5Proxy>, proxy_only: bool) -> Self {
+        self.proxy_only = proxy_only && proxy.is_some();
+        self.socks5_proxy = proxy;
+        self
+    }
+
+    // QUIC and WebRTC cannot carry the pnet handshake nor go through a SOCKS proxy
+    pub fn udp_transports_enabled(&self) -> bool {
+        self.pre_shared_key.is_none() && !self.proxy_only
+    }
+
+    // Direct TCP listening and hole punching would reveal our address in proxy-only mode
+    pub fn direct_enabled(&self) -> bool {
+        !self.proxy_only
+    }
+
+    pub fn wss_enabled(&self) -> bool {
+        self.wss_tls.is_some()
+    }
+}
+
+// TLS server config from a PEM certificate chain and private key, None (logged) if they do not load
+fn wss_server_tls(cert_path: &Path, key_path: &Path) -> Option<websocket::tls::Config> {
+    let certs = CertificateDer::pem_file_iter(cert_path).and_then(|certs| certs.collect::<Result<Vec<_>, _>>());
+    let key = PrivateKeyDer::from_pem_file(key_path);
+    match (certs, key) {
+        (Ok(certs), Ok(key)) if !certs.is_empty() => {
+            let key = websocket::tls::PrivateKey::new(key.secret_der().to_vec());
+            let certs = certs.iter().map(|c| websocket::tls::Certificate::new(c.to_vec()));
+            match websocket::tls::Config::new(key, certs) {
+                Ok(config) => {
+                    info!("WSS enabled with certificate {:?}", cert_path);
+                    Some(config)
+                },
+                Err(e) => {
+                    warn!("Invalid WSS certificate/key: {}", e);
+                    None
+                },
+            }
+        },
+        (Ok(_), Ok(_)) => {
+            warn!("No certificate found in {:?}", cert_path);
+            None
+        },
+        (Err(e), _) => {
+            warn!("Failed to read WSS certificate {:?}: {}", cert_path, e);
+            None
+        },
+        (_, Err(e)) => {
+            warn!("Failed to read WSS key {:?}: {}", key_path, e);
+            None
+        },
+    }
+}
+
+// Resolver settings, read once per transport. The configured fallback stands in where the
+// OS configuration is unreadable
+type Resolver = (dns::ResolverConfig, dns::ResolverOpts);
+
+fn resolver(fallback: DnsFallback) -> Resolver {
+    match hickory_resolver::system_conf::read_system_conf() {
+        Ok(resolver) => {
+            info!("[DNS] Using the system resolvers (fallback: {})", fallback);
+            resolver
+        },
+        Err(e) => {
+            info!("[DNS] System DNS configuration unavailable ({}), using the configured fallback: {}", e, fallback);
+            (fallback.resolver_config(), dns::ResolverOpts::default())
+        },
+    }
+}
+
+// Resolve /dns, /dns4 and /dns6 before dialing
+fn with_dns<T>(inner: T, resolver: &Resolver) -> dns::tokio::Transport<T> {
+    let (config, opts) = resolver.clone();
+    dns::tokio::Transport::custom(inner, config, opts)
+}
+
+// Noise + Yamux on top of a stream transport, behind the pnet handshake when a key is set
+pub fn authenticate<T>(
+    transport: T,
+    keypair: &identity::Keypair,
+    pre_shared_key: Option<PreSharedKey>,
+) -> Boxed<(PeerId, StreamMuxerBox)>
+where
+    T: Transport + Send + Unpin + 'static,
+    T::Output: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    T::Error: Send + Sync + 'static,
+    T::Dial: Send + 'static,
+    T::ListenerUpgrade: Send + 'static,
+{
+    let Some(key) = pre_shared_key else {
+        let upgraded = transport
+            .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+            .authenticate(noise::Config::new(keypair).unwrap())
+            .multiplex(yamux::Config::default())
+            .boxed();
+        return with_handshake_timeout(upgraded, "Handshake timed out");
+    };
+
+    let upgraded = transport
+        .and_then(move |socket, _| PnetConfig::new(key).handshake(socket))
+        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+        .authenticate(noise::Config::new(keypair).unwrap())
+        .multiplex(yamux::Config::default())
+        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+        .map_err(|e| {
+            use either::Either;
+            match e {
+                // With a different key both sides only see garbage after the pnet nonces, so
+                // the security negotiation is what fails
+                Either::Left(Either::Right(e)) => io::Error::other(format!(
+                    "Private network handshake failed, the peer probably uses a different pre-shared key ({})",
+                    e
+                )),
+                Either::Left(Either::Left(e)) => io::Error::other(e),
+                Either::Right(e) => io::Error::other(e),
+            }
+        })
+        .boxed();
+    // Garbage can also read as the length of a message that never arrives
+    with_handshake_timeout(upgraded, "Private network handshake timed out, the peer probably uses a different pre-shared key")
+}
+
+fn with_handshake_timeout(transport: Boxed<(PeerId, StreamMuxerBox)>, message: &'static str) -> Boxed<(PeerId, StreamMuxerBox)> {
+    TransportTimeout::new(transport, HANDSHAKE_TIMEOUT)
+        .map_err(move |e| match e {
+            TransportTimeoutError::Timeout => io::Error::new(io::ErrorKind::TimedOut, message),
+            TransportTimeoutError::TimerError(e) | TransportTimeoutError::Other(e) => e,
+        })
+        .boxed()
+}
+
+// Raw TCP streams: through the SOCKS5 proxy when configured (host names are resolved by the
+// proxy), plain TCP behind the DNS resolver otherwise. Plain TCP still listens unless proxy-only
+fn stream_transport(config: &TransportConfig, resolver: &Resolver) -> Boxed<tcp::tokio::TcpStream> {
+    match &config.socks5_proxy {
+        Some(proxy) if config.proxy_only => Socks5Transport::new(proxy.clone()).boxed(),
+        Some(proxy) => Socks5Transport::new(proxy.clone())
+            .or_transport(with_dns(tcp::tokio::Transport::default(), resolver))
+            .map(|either, _| match either {
+                Either::Left(res) => res,
+                Either::Right(res) => res,
+            })
+            .boxed(),
+        None => with_dns(tcp::tokio::Transport::default(), resolver).boxed(),
+    }
+}
+
+// Build hybrid transport (QUIC + WebRTC + TCP + WebSocket + Relay, optionally through SOCKS5)
 pub fn build_transport(
     keypair: &identity::Keypair,
     peer_id: PeerId,
+    config: &TransportConfig,
 ) -> (Boxed<(PeerId, StreamMuxerBox)>, relay::client::Behaviour) {
 
     // Create relay client
     let (relay_transport, relay_client) = relay::client::new(peer_id);
 
     // Relay transport
-    let relay_transport = relay_transport
-        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
-        .authenticate(noise::Config::new(keypair).unwrap())
-        .multiplex(yamux::Config::default())
-        .boxed();
+    let psk = config.pre_shared_key;
+    if let Some(key) = psk {
+        info!("Private network enabled, pre-shared key fingerprint {}", key.fingerprint());
+    }
+    let relay_transport = authenticate(relay_transport, keypair, psk);
+
+    let resolver = resolver(config.dns_fallback);
 
     // TCP transport
-    let tcp_transport = tcp::tokio::Transport::default()
-        .upgrade(libp2p::core::upgrade::Version::V1Lazy)
-        .authenticate(noise::Config::new(keypair).unwrap())
-        .multiplex(yamux::Config::default())
-        .boxed();
+    if let Some(proxy) = &config.socks5_proxy {
+        info!("Outbound TCP through SOCKS5 proxy {} (proxy-only: {})", proxy.addr, config.proxy_only);
+    }
+    let tcp_transport = authenticate(stream_transport(config, &resolver), keypair, psk);
+
+    // WebSocket transport (/ws and /wss), looks like HTTPS to middleboxes
+    // DNS sits below WebSocket so /dns/<host>/tcp/443/wss keeps the host name for TLS (SNI)
+    let mut ws_config = websocket::WsConfig::new(stream_transport(config, &resolver));
+    ws_config.set_tls_config(config.wss_tls.clone().unwrap_or_else(websocket::tls::Config::client));
+    let ws_transport = authenticate(ws_config, keypair, psk);
 
     // QUIC transport
-    let quic_transport = libp2p::quic::tokio::Transport::new(libp2p::quic::Config::new(keypair))
-        .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
-        .boxed();
+    let quic_transport = if config.udp_transports_enabled() {
+        libp2p::quic::tokio::Transport::new(libp2p::quic::Config::new(keypair))
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+            .boxed()
+    } else {
+        libp2p::core::transport::dummy::DummyTransport::new().boxed()
+    };
+
+    // WebRTC-direct transport, the only one browsers can dial without a certificate authority
+    let webrtc_transport = match &config.webrtc_certificate {
+        Some(certificate) if config.udp_transports_enabled() => libp2p_webrtc::tokio::Transport::new(keypair.clone(), certificate.clone())
+            .map(|(peer_id, conn), _| (peer_id, StreamMuxerBox::new(conn)))
+            .boxed(),
+        _ => libp2p::core::transport::dummy::DummyTransport::new().boxed(),
+    };
 
     // Combination 1
-    let tcp_plus_relay = tcp_transport
-        .or_transport(relay_transport)
+    let quic_plus_webrtc = quic_transport
+        .or_transport(webrtc_transport)
+        .map(|either, _| match either {
+            Either::Left(res) => res,
+            Either::Right(res) => res,
+        });
+
+    // Combination 2. TCP and WebSocket resolve DNS names themselves (or leave it to the proxy)
+    let ws_plus_tcp = ws_transport
+        .or_transport(tcp_transport)
+        .map(|either, _| match either {
+            Either::Left(res) => res,
+            Either::Right(res) => res,
+        });
+
+    // Combination 3. Without UDP transports there is nothing to resolve for, and resolving
+    // locally would leak names in proxy-only mode
+    let udp_transport = if config.udp_transports_enabled() {
+        with_dns(quic_plus_webrtc, &resolver).boxed()
+    } else {
+        quic_plus_webrtc.boxed()
+    };
+    let streams_plus_udp = ws_plus_tcp
+        .or_transport(udp_transport)
         .map(|either, _| match either {
             Either::Left(res) => res,
             Either::Right(res) => res,
         });
 
-    // Combination 2
-    let transport = quic_transport
-        .or_transport(tcp_plus_relay)
+    // Combination 4. Circuits need no resolver, the relay itself is dialed through the swarm
+    let transport = streams_plus_udp
+        .or_transport(relay_transport)
         .map(|either, _| match either {
             Either::Left(res) => res,
             Either::Right(res) => res,
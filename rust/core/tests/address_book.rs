@@ -0,0 +1,140 @@
+// Address book: caps, ageing and persistence across restarts
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use libp2p::{identity::Keypair, Multiaddr, PeerId};
+use p2p_core::address_book::{AddressBook, MAX_ADDRESSES_PER_PEER, MAX_AGE, MAX_PEERS};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+fn addr(port: usize) -> Multiaddr {
+    format!("/ip4/192.0.2.1/tcp/{}", port).parse().unwrap()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+// Instance name unique to the test, the file is removed again when dropped
+struct BookFile {
+    instance: String,
+}
+
+impl BookFile {
+    fn new() -> Self {
+        Self { instance: format!("test_{}", peer()) }
+    }
+
+    fn path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!("address_book_{}.json", self.instance))
+    }
+
+    fn load(&self) -> AddressBook {
+        AddressBook::load(&std::env::temp_dir().to_string_lossy(), &self.instance)
+    }
+}
+
+impl Drop for BookFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.path());
+    }
+}
+
+#[test]
+fn addresses_survive_restart() {
+    let file = BookFile::new();
+    let (dialed, learned) = (peer(), peer());
+
+    let mut book = file.load();
+    assert!(book.is_empty());
+    // Stored without the /p2p suffix
+    book.record_success(dialed, Some(&format!("{}/p2p/{}", addr(1), dialed).parse().unwrap()));
+    book.add_address(learned, &addr(2));
+    book.save_if_dirty();
+
+    let book = file.load();
+    assert_eq!(book.len(), 2);
+    assert_eq!(book.peers()[0], (dialed, vec![addr(1)]));
+    // Only peers we connected to before are dialed on startup
+    assert_eq!(book.dial_candidates(10), vec![(dialed, vec![addr(1)])]);
+}
+
+#[test]
+fn newest_addresses_are_kept() {
+    let file = BookFile::new();
+    let remote = peer();
+
+    let mut book = file.load();
+    for port in 0..MAX_ADDRESSES_PER_PEER + 2 {
+        book.add_address(remote, &addr(port));
+    }
+    // Learned again, moves to the front instead of repeating
+    book.add_address(remote, &addr(5));
+
+    let addrs = &book.peers()[0].1;
+    assert_eq!(addrs.len(), MAX_ADDRESSES_PER_PEER);
+    assert_eq!(addrs[0], addr(5));
+    assert_eq!(addrs[1], addr(MAX_ADDRESSES_PER_PEER + 1));
+    assert!(!addrs.contains(&addr(0)) && !addrs.contains(&addr(1)));
+}
+
+#[test]
+fn least_successful_peers_are_dropped_past_the_cap() {
+    let file = BookFile::new();
+    let mut book = file.load();
+    let reached: Vec<PeerId> = (0..MAX_PEERS).map(|_| peer()).collect();
+    for (i, remote) in reached.iter().enumerate() {
+        book.record_success(*remote, Some(&addr(i)));
+    }
+    for i in 0..8 {
+        book.add_address(peer(), &addr(i));
+    }
+    assert_eq!(book.len(), MAX_PEERS + 8);
+    book.save_if_dirty();
+    assert_eq!(book.len(), MAX_PEERS);
+
+    let book = file.load();
+    assert_eq!(book.len(), MAX_PEERS);
+    assert!(book.peers().iter().all(|(remote, _)| reached.contains(remote)));
+}
+
+#[test]
+fn peers_unseen_for_a_month_are_forgotten() {
+    let file = BookFile::new();
+    let (stale, reached_lately, fresh) = (peer(), peer(), peer());
+    let old = unix_now() - MAX_AGE.as_secs() - 60;
+    let recent = unix_now() - MAX_AGE.as_secs() + 3600;
+    let text = format!(
+        r#"{{"{}":{{"addresses":["{}"],"last_seen":{},"last_success":null}},"{}":{{"addresses":["{}"],"last_seen":{},"last_success":{}}},"{}":{{"addresses":["{}"],"last_seen":{},"last_success":null}}}}"#,
+        stale, addr(1), old,
+        reached_lately, addr(2), old, recent,
+        fresh, addr(3), recent,
+    );
+    fs::write(file.path(), text).unwrap();
+
+    let book = file.load();
+    let peers: Vec<PeerId> = book.peers().into_iter().map(|(remote, _)| remote).collect();
+    assert_eq!(peers, vec![reached_lately, fresh]);
+}
+
+#[test]
+fn corrupt_file_starts_empty() {
+    let file = BookFile::new();
+    fs::write(file.path(), "{not json").unwrap();
+    assert!(file.load().is_empty());
+}
+
+#[test]
+fn removed_peers_stay_removed() {
+    let file = BookFile::new();
+    let remote = peer();
+
+    let mut book = file.load();
+    book.add_address(remote, &addr(1));
+    book.save_if_dirty();
+    book.remove(&remote);
+    book.save_if_dirty();
+    assert!(file.load().is_empty());
+}
@@ -0,0 +1,102 @@
+// Blocked peers file: parsing, saving and picking up edits made by hand
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use libp2p::{identity::Keypair, PeerId};
+use p2p_core::block_list::BlockList;
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+// Instance name unique to the test, the file is removed again when dropped
+struct ListFile {
+    instance: String,
+}
+
+impl ListFile {
+    fn new() -> Self {
+        Self { instance: format!("test_{}", peer()) }
+    }
+
+    fn storage(&self) -> String {
+        std::env::temp_dir().to_string_lossy().into_owned()
+    }
+
+    fn path(&self) -> PathBuf {
+        std::env::temp_dir().join(format!("blocked_peers_{}.txt", self.instance))
+    }
+
+    fn load(&self) -> BlockList {
+        BlockList::load(&self.storage(), &self.instance)
+    }
+
+    // Edits by hand. The modification time moves on explicitly, file systems may only keep seconds
+    fn write(&self, text: &str, age: Duration) {
+        fs::write(self.path(), text).unwrap();
+        let file = fs::File::options().write(true).open(self.path()).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+}
+
+impl Drop for ListFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.path());
+    }
+}
+
+#[test]
+fn comments_blank_and_invalid_lines_are_skipped() {
+    let file = ListFile::new();
+    let (a, b) = (peer(), peer());
+    file.write(&format!("# blocked peers\n\n  {}  \nnot-a-peer-id\n#{}\n{}\n", a, peer(), b), Duration::ZERO);
+
+    let list = file.load();
+    assert!(list.contains(&a));
+    assert!(list.contains(&b));
+    assert_eq!(list.peers().count(), 2);
+}
+
+#[test]
+fn missing_file_is_an_empty_list() {
+    let file = ListFile::new();
+    let mut list = file.load();
+    assert_eq!(list.peers().count(), 0);
+    assert!(list.reload().is_none());
+}
+
+#[test]
+fn changes_are_saved() {
+    let file = ListFile::new();
+    let (a, b) = (peer(), peer());
+
+    let mut list = file.load();
+    assert!(list.block(a));
+    assert!(!list.block(a));
+    assert!(list.block(b));
+    assert!(list.unblock(&b));
+    assert!(!list.unblock(&b));
+    // Our own save is not reported as an edit
+    assert!(list.reload().is_none());
+
+    let list = file.load();
+    assert!(list.contains(&a));
+    assert!(!list.contains(&b));
+}
+
+#[test]
+fn edits_by_hand_are_picked_up_once() {
+    let file = ListFile::new();
+    let (kept, removed, added) = (peer(), peer(), peer());
+    file.write(&format!("{}\n{}\n", kept, removed), Duration::from_secs(60));
+
+    let mut list = file.load();
+    assert!(list.reload().is_none());
+
+    file.write(&format!("{}\n{}\n", kept, added), Duration::ZERO);
+    let (blocked, unblocked) = list.reload().expect("Edit not picked up");
+    assert_eq!(blocked, vec![added]);
+    assert_eq!(unblocked, vec![removed]);
+    assert!(list.contains(&added) && !list.contains(&removed));
+    assert!(list.reload().is_none());
+}
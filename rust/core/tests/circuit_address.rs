@@ -0,0 +1,106 @@
+// Circuit address construction across relay address shapes
+use libp2p::{identity::Keypair, Multiaddr, PeerId};
+use p2p_core::circuit::{circuit_address, circuit_addresses, is_dialable_relay_address, relay_base, reservation_address};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+fn addr(s: &str) -> Multiaddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn keeps_full_transport_stack() {
+    let relay = peer();
+    let target = peer();
+
+    let shapes = [
+        "/ip4/203.0.113.7/tcp/4001",
+        "/ip4/203.0.113.7/udp/4001/quic-v1",
+        "/ip6/2001:db8::7/tcp/4001",
+        "/ip6/2001:db8::7/udp/4001/quic-v1",
+        "/dns4/relay.example.com/tcp/4001",
+        "/dns6/relay.example.com/udp/4001/quic-v1",
+        "/dns/relay.example.com/tcp/443/wss",
+        "/ip4/203.0.113.7/tcp/443/ws",
+    ];
+    for shape in shapes {
+        let expected = addr(&format!("{shape}/p2p/{relay}/p2p-circuit/p2p/{target}"));
+
+        // With and without the relay id, with and without a trailing /p2p-circuit
+        for input in [
+            shape.to_string(),
+            format!("{shape}/p2p/{relay}"),
+            format!("{shape}/p2p/{relay}/p2p-circuit"),
+        ] {
+            assert_eq!(circuit_address(&addr(&input), relay, target), Some(expected.clone()), "{input}");
+        }
+    }
+}
+
+#[test]
+fn reservation_and_base_addresses() {
+    let relay = peer();
+    let input = addr(&format!("/dns4/relay.example.com/udp/4001/quic-v1/p2p/{relay}/p2p-circuit"));
+
+    assert_eq!(
+        relay_base(&input, relay),
+        Some(addr(&format!("/dns4/relay.example.com/udp/4001/quic-v1/p2p/{relay}")))
+    );
+    assert_eq!(
+        reservation_address(&input, relay),
+        Some(addr(&format!("/dns4/relay.example.com/udp/4001/quic-v1/p2p/{relay}/p2p-circuit")))
+    );
+}
+
+#[test]
+fn rejects_unusable_relay_addresses() {
+    let relay = peer();
+    let other = peer();
+    let target = peer();
+
+    // Address of another peer
+    assert_eq!(circuit_address(&addr(&format!("/ip4/203.0.113.7/tcp/4001/p2p/{other}")), relay, target), None);
+    // Relay only reachable through another relay
+    let nested = addr(&format!("/ip4/203.0.113.7/tcp/4001/p2p/{other}/p2p-circuit/p2p/{relay}"));
+    assert_eq!(circuit_address(&nested, relay, target), None);
+    // No transport part at all
+    assert_eq!(circuit_address(&addr(&format!("/p2p/{relay}")), relay, target), None);
+    // Circuit to the relay itself
+    assert_eq!(circuit_address(&addr("/ip4/203.0.113.7/tcp/4001"), relay, relay), None);
+}
+
+#[test]
+fn tries_every_relay_address_once() {
+    let relay_a = peer();
+    let relay_b = peer();
+    let target = peer();
+
+    let a_tcp = addr(&format!("/dns4/relay-a.example.com/tcp/4001/p2p/{relay_a}"));
+    let a_tcp_circuit = addr(&format!("/dns4/relay-a.example.com/tcp/4001/p2p/{relay_a}/p2p-circuit"));
+    let a_quic = addr("/ip4/203.0.113.7/udp/4001/quic-v1");
+    let b_ip6 = addr("/ip6/2001:db8::8/tcp/4001");
+    let foreign = addr(&format!("/ip4/203.0.113.9/tcp/4001/p2p/{target}"));
+
+    let circuits = circuit_addresses(
+        [(relay_a, &a_tcp), (relay_a, &a_tcp_circuit), (relay_a, &a_quic), (relay_b, &b_ip6), (relay_b, &foreign)],
+        target,
+    );
+
+    assert_eq!(circuits, vec![
+        addr(&format!("/dns4/relay-a.example.com/tcp/4001/p2p/{relay_a}/p2p-circuit/p2p/{target}")),
+        addr(&format!("/ip4/203.0.113.7/udp/4001/quic-v1/p2p/{relay_a}/p2p-circuit/p2p/{target}")),
+        addr(&format!("/ip6/2001:db8::8/tcp/4001/p2p/{relay_b}/p2p-circuit/p2p/{target}")),
+    ]);
+}
+
+#[test]
+fn filters_local_relay_listen_addresses() {
+    assert!(is_dialable_relay_address(&addr("/ip4/203.0.113.7/udp/4001/quic-v1")));
+    assert!(is_dialable_relay_address(&addr("/dns4/relay.example.com/tcp/443/wss")));
+    assert!(is_dialable_relay_address(&addr("/ip6/2001:db8::7/tcp/4001")));
+    assert!(!is_dialable_relay_address(&addr("/ip4/127.0.0.1/tcp/4001")));
+    assert!(!is_dialable_relay_address(&addr("/ip6/::1/udp/4001/quic-v1")));
+    assert!(!is_dialable_relay_address(&addr("/ip4/0.0.0.0/tcp/4001")));
+}
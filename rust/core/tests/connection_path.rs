@@ -0,0 +1,77 @@
+// Connection path per peer: relayed -> hole punching -> direct / failed
+use std::time::{Duration, Instant};
+use libp2p::{identity::Keypair, swarm::ConnectionId, Multiaddr, PeerId};
+use p2p_core::connection_path::{ConnectionPaths, PathState, HOLE_PUNCH_TIMEOUT};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+fn direct() -> Multiaddr {
+    "/ip4/192.0.2.1/udp/4001/quic-v1".parse().unwrap()
+}
+
+fn relayed() -> Multiaddr {
+    let relay = peer();
+    format!("/ip4/192.0.2.2/tcp/4001/p2p/{}/p2p-circuit", relay).parse().unwrap()
+}
+
+#[test]
+fn relayed_connection_upgrades_to_direct() {
+    let mut paths = ConnectionPaths::new();
+    let remote = peer();
+
+    assert_eq!(paths.on_established(remote, ConnectionId::new_unchecked(1), &relayed(), true), Some(PathState::HolePunching));
+    assert_eq!(paths.on_upgrade(&remote, true), Some(PathState::Direct));
+    assert_eq!(paths.on_established(remote, ConnectionId::new_unchecked(2), &direct(), true), None);
+    assert_eq!(paths.transport(&remote, ConnectionId::new_unchecked(2)), Some("QUIC"));
+
+    // Losing the direct connection falls back to the relay
+    assert_eq!(paths.on_closed(&remote, ConnectionId::new_unchecked(2)), Some(PathState::Relayed));
+    assert_eq!(paths.on_closed(&remote, ConnectionId::new_unchecked(1)), None);
+    assert_eq!(paths.get(&remote), None);
+}
+
+#[test]
+fn failed_upgrade_stays_relayed() {
+    let mut paths = ConnectionPaths::new();
+    let remote = peer();
+
+    paths.on_established(remote, ConnectionId::new_unchecked(1), &relayed(), true);
+    assert_eq!(paths.on_upgrade(&remote, false), Some(PathState::Failed));
+    // A second report changes nothing
+    assert_eq!(paths.on_upgrade(&remote, false), None);
+    assert_eq!(paths.get(&remote), Some(PathState::Failed));
+}
+
+#[test]
+fn relayed_without_dcutr() {
+    let mut paths = ConnectionPaths::new();
+    let remote = peer();
+
+    assert_eq!(paths.on_established(remote, ConnectionId::new_unchecked(1), &relayed(), false), Some(PathState::Relayed));
+    // Already direct, an extra relayed connection does not change the path
+    let other = peer();
+    paths.on_established(other, ConnectionId::new_unchecked(2), &direct(), true);
+    assert_eq!(paths.on_established(other, ConnectionId::new_unchecked(3), &relayed(), true), None);
+    assert_eq!(paths.get(&other), Some(PathState::Direct));
+}
+
+#[test]
+fn hole_punching_times_out_after_a_minute() {
+    assert_eq!(HOLE_PUNCH_TIMEOUT, Duration::from_secs(60));
+    let mut paths = ConnectionPaths::new();
+    let (stalled, upgraded) = (peer(), peer());
+
+    paths.on_established(stalled, ConnectionId::new_unchecked(1), &relayed(), true);
+    paths.on_established(upgraded, ConnectionId::new_unchecked(2), &relayed(), true);
+    paths.on_upgrade(&upgraded, true);
+    let started = paths.since(&stalled).unwrap();
+
+    assert!(paths.expire_hole_punching(started + HOLE_PUNCH_TIMEOUT - Duration::from_secs(1)).is_empty());
+    assert_eq!(paths.expire_hole_punching(started + HOLE_PUNCH_TIMEOUT), vec![stalled]);
+    assert_eq!(paths.get(&stalled), Some(PathState::Relayed));
+    assert_eq!(paths.get(&upgraded), Some(PathState::Direct));
+    // Expired once only
+    assert!(paths.expire_hole_punching(Instant::now() + HOLE_PUNCH_TIMEOUT * 2).is_empty());
+}
@@ -0,0 +1,110 @@
+// Hole punching counters and the retry schedule after DCUtR gave up
+use std::time::{Duration, Instant};
+use libp2p::{identity::Keypair, Multiaddr, PeerId};
+use p2p_core::hole_punch::{transport_name, HolePunching, MAX_HOLE_PUNCH_RETRIES};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+// Jitter keeps every delay between half and all of the base
+fn assert_within(delay: Duration, base: Duration) {
+    assert!(delay >= base / 2 && delay <= base, "{:?} outside {:?}..={:?}", delay, base / 2, base);
+}
+
+#[test]
+fn counters_split_by_nat_and_transport() {
+    let mut hole_punching = HolePunching::new();
+    let (a, b, c) = (peer(), peer(), peer());
+
+    hole_punching.on_started(a, "PRIVATE", "TCP");
+    hole_punching.on_started(b, "PRIVATE", "TCP");
+    hole_punching.on_started(c, "PUBLIC", "QUIC");
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(hole_punching.on_succeeded(&a, "QUIC").is_some());
+    assert!(hole_punching.on_failed(b).0.is_some());
+    assert!(hole_punching.on_succeeded(&c, "QUIC").is_some());
+
+    let summary = hole_punching.summary();
+    assert_eq!((summary.total.attempts, summary.total.successes, summary.total.failures), (3, 2, 1));
+    assert!((summary.total.success_rate() - 2.0 / 3.0).abs() < 1e-9);
+    assert!(summary.total.avg_ms >= 20, "average {} ms", summary.total.avg_ms);
+
+    let private = &summary.by_local_nat["PRIVATE"];
+    assert_eq!((private.successes, private.failures), (1, 1));
+    assert_eq!(summary.by_local_nat["PUBLIC"].successes, 1);
+    // Successes count the direct transport, failures the relayed one
+    assert_eq!(summary.by_transport["QUIC"].successes, 2);
+    assert_eq!(summary.by_transport["TCP"].failures, 1);
+}
+
+#[test]
+fn unseen_and_abandoned_attempts_are_not_counted() {
+    let mut hole_punching = HolePunching::new();
+    let (a, b) = (peer(), peer());
+
+    assert!(hole_punching.on_succeeded(&a, "TCP").is_none());
+    hole_punching.on_started(b, "UNKNOWN", "TCP");
+    hole_punching.abandon(&b);
+    assert!(hole_punching.on_failed(b).0.is_none());
+
+    assert_eq!(hole_punching.summary().total.attempts, 0);
+    assert_eq!(hole_punching.summary().total.success_rate(), 0.0);
+}
+
+#[test]
+fn retries_back_off_and_run_out() {
+    let mut hole_punching = HolePunching::new();
+    let remote = peer();
+    let later = || Instant::now() + Duration::from_secs(3600);
+
+    let mut base = Duration::from_secs(30);
+    for _ in 0..MAX_HOLE_PUNCH_RETRIES {
+        let delay = hole_punching.on_failed(remote).1.expect("Retry not scheduled");
+        assert_within(delay, base.min(Duration::from_secs(600)));
+        // Another failure of the same attempt keeps the scheduled retry
+        assert!(hole_punching.on_failed(remote).1.unwrap() <= delay);
+        assert!(hole_punching.due_retries(Instant::now()).is_empty());
+        assert_eq!(hole_punching.due_retries(later()), vec![remote]);
+        base *= 2;
+    }
+    assert!(hole_punching.on_failed(remote).1.is_none());
+    assert!(hole_punching.due_retries(later()).is_empty());
+    assert_eq!(hole_punching.summary().retries, MAX_HOLE_PUNCH_RETRIES as u64);
+}
+
+#[test]
+fn abandoning_keeps_the_retry() {
+    let mut hole_punching = HolePunching::new();
+    let remote = peer();
+
+    hole_punching.on_failed(remote);
+    // Disconnected in between, the retry still comes due
+    hole_punching.abandon(&remote);
+    assert_eq!(hole_punching.due_retries(Instant::now() + Duration::from_secs(60)), vec![remote]);
+}
+
+#[test]
+fn success_and_forget_clear_the_retry() {
+    let mut hole_punching = HolePunching::new();
+    let (upgraded, blocked) = (peer(), peer());
+
+    hole_punching.on_failed(upgraded);
+    hole_punching.on_failed(blocked);
+    hole_punching.on_succeeded(&upgraded, "TCP");
+    hole_punching.forget(&blocked);
+    assert!(hole_punching.due_retries(Instant::now() + Duration::from_secs(3600)).is_empty());
+
+    // Schedule starts over after a success
+    assert_within(hole_punching.on_failed(upgraded).1.unwrap(), Duration::from_secs(30));
+}
+
+#[test]
+fn transport_names() {
+    let name = |addr: &str| transport_name(&addr.parse::<Multiaddr>().unwrap());
+    assert_eq!(name("/ip4/192.0.2.1/tcp/4001"), "TCP");
+    assert_eq!(name("/ip4/192.0.2.1/udp/4001/quic-v1"), "QUIC");
+    assert_eq!(name("/ip4/192.0.2.1/tcp/443/ws"), "WEBSOCKET");
+    assert_eq!(name("/ip4/192.0.2.1/udp/4001/webrtc-direct"), "WEBRTC");
+    assert_eq!(name("/ip4/192.0.2.1"), "OTHER");
+}
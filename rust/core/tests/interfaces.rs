@@ -0,0 +1,55 @@
+// Network change detection: address diff and the deferral of quick successive changes
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use p2p_core::interfaces::InterfaceWatch;
+
+fn addrs(list: &[&str]) -> BTreeSet<IpAddr> {
+    list.iter().map(|a| a.parse().unwrap()).collect()
+}
+
+#[test]
+fn address_changes_are_reported_once() {
+    let mut watch = InterfaceWatch::with_addrs(addrs(&["192.168.1.20", "fe80::1"]));
+
+    // Same set in another order
+    assert!(!watch.update(addrs(&["fe80::1", "192.168.1.20"])));
+    // Wi-Fi gone, mobile data up
+    assert!(watch.update(addrs(&["10.64.0.7", "fe80::1"])));
+    assert!(!watch.update(addrs(&["10.64.0.7", "fe80::1"])));
+    // A VPN adds an address
+    assert!(watch.update(addrs(&["10.64.0.7", "10.8.0.2", "fe80::1"])));
+    // All interfaces down
+    assert!(watch.update(BTreeSet::new()));
+}
+
+#[test]
+fn change_right_after_another_is_deferred() {
+    let mut watch = InterfaceWatch::with_addrs(BTreeSet::new());
+    let start = Instant::now();
+
+    assert!(watch.should_handle(start));
+    // Nothing deferred yet
+    assert!(!watch.deferred_due(start + Duration::from_secs(10)));
+
+    assert!(!watch.should_handle(start + Duration::from_secs(2)));
+    assert!(!watch.deferred_due(start + Duration::from_secs(4)));
+    assert!(watch.deferred_due(start + Duration::from_secs(5)));
+    // Reported once
+    assert!(!watch.deferred_due(start + Duration::from_secs(6)));
+
+    // Handled when it comes back through `should_handle`
+    assert!(watch.should_handle(start + Duration::from_secs(5)));
+}
+
+#[test]
+fn deferred_change_is_dropped_once_handled() {
+    let mut watch = InterfaceWatch::with_addrs(BTreeSet::new());
+    let start = Instant::now();
+
+    watch.should_handle(start);
+    watch.should_handle(start + Duration::from_secs(1));
+    // A later change covers the deferred one
+    assert!(watch.should_handle(start + Duration::from_secs(7)));
+    assert!(!watch.deferred_due(start + Duration::from_secs(20)));
+}
@@ -0,0 +1,69 @@
+// Connection and memory limits: defaults and the P2P_* environment overrides
+use std::sync::Mutex;
+use p2p_core::limits::Limits;
+
+const VARS: [&str; 4] = ["P2P_MAX_CONNECTIONS", "P2P_MAX_CONNECTIONS_PER_PEER", "P2P_MAX_PENDING_INCOMING", "P2P_MAX_MEMORY_PERCENT"];
+
+// The environment is shared by the whole process, tests touching it take turns
+static ENV: Mutex<()> = Mutex::new(());
+
+// Runs `check` with exactly the given overrides set
+fn with_env(values: [Option<&str>; 4], check: impl FnOnce()) {
+    let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    for (name, value) in VARS.iter().zip(values) {
+        match value {
+            Some(value) => std::env::set_var(name, value),
+            None => std::env::remove_var(name),
+        }
+    }
+    check();
+    for name in VARS {
+        std::env::remove_var(name);
+    }
+}
+
+#[test]
+fn missing_overrides_keep_the_defaults() {
+    with_env([None; 4], || {
+        assert_eq!(Limits::node().with_env_overrides(), Limits::node());
+        assert_eq!(Limits::relay().with_env_overrides(), Limits::relay());
+    });
+    assert_eq!(Limits::node().to_string(), "max connections 128, per peer 4, pending incoming 32, memory 90%");
+}
+
+#[test]
+fn valid_overrides_apply() {
+    with_env([Some("10"), Some(" 2 "), Some("5"), Some("50")], || {
+        let limits = Limits::relay().with_env_overrides();
+        assert_eq!(limits.max_established, Some(10));
+        assert_eq!(limits.max_established_per_peer, Some(2));
+        assert_eq!(limits.max_pending_incoming, Some(5));
+        assert_eq!(limits.max_memory_fraction, Some(0.5));
+    });
+}
+
+#[test]
+fn zero_disables_a_limit() {
+    with_env([Some("0"), None, Some("0"), Some("0")], || {
+        let limits = Limits::node().with_env_overrides();
+        assert_eq!(limits.max_established, None);
+        assert_eq!(limits.max_established_per_peer, Some(4));
+        assert_eq!(limits.max_pending_incoming, None);
+        assert_eq!(limits.max_memory_fraction, None);
+        assert_eq!(limits.to_string(), "max connections unlimited, per peer 4, pending incoming unlimited, memory unlimited");
+    });
+}
+
+#[test]
+fn memory_is_capped_at_all_of_it() {
+    with_env([None, None, None, Some("250")], || {
+        assert_eq!(Limits::node().with_env_overrides().max_memory_fraction, Some(1.0));
+    });
+}
+
+#[test]
+fn invalid_overrides_are_ignored() {
+    with_env([Some("many"), Some("-1"), Some(""), Some("half")], || {
+        assert_eq!(Limits::node().with_env_overrides(), Limits::node());
+    });
+}
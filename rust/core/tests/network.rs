@@ -0,0 +1,38 @@
+// Network ids and the names derived from them
+use libp2p::rendezvous::MAX_NAMESPACE;
+use p2p_core::network::Network;
+
+#[test]
+fn default_network_keeps_the_original_names() {
+    let network = Network::new("  ").unwrap();
+    assert_eq!(network, Network::default());
+    assert_eq!(network.topic_name(), "p2p-chat-global");
+    assert_eq!(network.kad_protocol().as_ref(), "/p2p_msg/kad/1.0.0");
+    assert_eq!(network.relay_provider_key_name(), "/p2p_msg/relays/1.0.0");
+}
+
+#[test]
+fn named_network_is_namespaced() {
+    let network = Network::new(" staging-2.eu_west ").unwrap();
+    assert_eq!(network.id(), "staging-2.eu_west");
+    assert_eq!(network.topic_name(), "p2p-chat-global-staging-2.eu_west");
+    assert_eq!(network.kad_protocol().as_ref(), "/p2p_msg/staging-2.eu_west/kad/1.0.0");
+    assert_eq!(network.identify_protocol(), "/p2p_msg/staging-2.eu_west/id/1.0.0");
+    assert_eq!(network.rendezvous_namespace(Some("lobby")).unwrap().to_string(), "p2p-chat/staging-2.eu_west/room/lobby");
+}
+
+#[test]
+fn ids_outside_the_allowed_characters_are_rejected() {
+    for id in ["staging/prod", "a b", "net:1", "prod\tstaging", "ünï", "room*"] {
+        assert!(Network::new(id).is_err(), "{:?} accepted", id);
+    }
+}
+
+#[test]
+fn ids_must_fit_in_a_rendezvous_namespace() {
+    // "p2p-chat/" comes first
+    let longest = "a".repeat(MAX_NAMESPACE - "p2p-chat/".len());
+    assert!(Network::new(&longest).is_ok());
+    let error = Network::new(&format!("{}a", longest)).unwrap_err();
+    assert!(error.contains("too long"), "{}", error);
+}
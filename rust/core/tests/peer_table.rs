@@ -0,0 +1,167 @@
+// Discovery source bookkeeping of the peer table
+use std::time::{Duration, Instant};
+use libp2p::{identity::Keypair, PeerId};
+use p2p_core::peer_table::{DepartureReason, DiscoverySource, PeerTable};
+
+const TTL: Duration = Duration::from_secs(120);
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+#[test]
+fn first_source_marks_online() {
+    let mut table = PeerTable::new();
+    let p = peer();
+    let now = Instant::now();
+
+    assert!(table.mark_online(p, DiscoverySource::Mdns, now + TTL));
+    assert!(!table.mark_online(p, DiscoverySource::Gossip, now + TTL));
+    assert_eq!(table.get(&p).unwrap().sources.len(), 2);
+}
+
+#[test]
+fn closed_connection_takes_away_only_itself() {
+    let mut table = PeerTable::new();
+    let p = peer();
+    let now = Instant::now();
+
+    table.mark_online(p, DiscoverySource::Connection, now + TTL);
+    table.mark_online(p, DiscoverySource::Gossip, now + TTL);
+    table.mark_online(p, DiscoverySource::Presence, now + TTL);
+
+    // A still valid presence record keeps the peer online, no PEER- / PEER+ flapping
+    assert!(table.remove(&p, DepartureReason::Closed).is_none());
+    let sources: Vec<_> = table.get(&p).unwrap().sources.iter().copied().collect();
+    assert_eq!(sources, vec![DiscoverySource::Gossip, DiscoverySource::Presence]);
+
+    // Until the record lapses and the lookup finds nothing newer
+    assert_eq!(table.lapsed(now + TTL), vec![p]);
+    assert!(table.remove(&p, DepartureReason::Timeout).is_some());
+}
+
+#[test]
+fn closed_connection_alone_goes_offline() {
+    let mut table = PeerTable::new();
+    let p = peer();
+
+    table.mark_online(p, DiscoverySource::Connection, Instant::now() + TTL);
+    let entry = table.remove(&p, DepartureReason::Closed).expect("No source left after the connection closed");
+    assert!(entry.sources.is_empty());
+    assert!(!table.contains(&p));
+}
+
+#[test]
+fn mdns_outlives_closed_connection() {
+    let mut table = PeerTable::new();
+    let p = peer();
+    let now = Instant::now();
+
+    table.mark_online(p, DiscoverySource::Mdns, now + TTL);
+    table.mark_online(p, DiscoverySource::Connection, now + TTL);
+
+    assert!(table.remove(&p, DepartureReason::Closed).is_none());
+    assert_eq!(table.get(&p).unwrap().sources.iter().copied().collect::<Vec<_>>(), vec![DiscoverySource::Mdns]);
+
+    assert!(table.remove(&p, DepartureReason::MdnsExpired).is_some());
+    assert!(!table.contains(&p));
+}
+
+#[test]
+fn mdns_expiry_keeps_other_sources() {
+    let mut table = PeerTable::new();
+    let p = peer();
+    let now = Instant::now();
+
+    table.mark_online(p, DiscoverySource::Mdns, now + TTL);
+    table.mark_online(p, DiscoverySource::Connection, now + TTL);
+
+    assert!(table.remove(&p, DepartureReason::MdnsExpired).is_none());
+    assert!(table.get(&p).unwrap().sources.contains(&DiscoverySource::Connection));
+}
+
+#[test]
+fn unroutable_takes_away_kademlia_only() {
+    let mut table = PeerTable::new();
+    let p = peer();
+    let q = peer();
+    let now = Instant::now();
+
+    table.mark_online(p, DiscoverySource::Kademlia, now + TTL);
+    table.mark_online(q, DiscoverySource::Kademlia, now + TTL);
+    table.mark_online(q, DiscoverySource::Connection, now + TTL);
+
+    assert!(table.remove(&p, DepartureReason::Unroutable).is_some());
+    assert!(table.remove(&q, DepartureReason::Unroutable).is_none());
+    assert!(!table.get(&q).unwrap().sources.contains(&DiscoverySource::Kademlia));
+}
+
+#[test]
+fn expiring_sources_lapse_with_deadline() {
+    let mut table = PeerTable::new();
+    let gossip = peer();
+    let presence = peer();
+    let kademlia = peer();
+    let now = Instant::now();
+
+    table.mark_online(gossip, DiscoverySource::Gossip, now + TTL);
+    table.mark_online(presence, DiscoverySource::Presence, now + TTL);
+    table.mark_online(kademlia, DiscoverySource::Kademlia, now + TTL);
+
+    assert!(table.lapsed(now).is_empty());
+
+    let mut lapsed = table.lapsed(now + TTL);
+    lapsed.sort();
+    let mut expected = vec![gossip, presence, kademlia];
+    expected.sort();
+    assert_eq!(lapsed, expected);
+    // Listed until the lookup decides
+    assert!(table.contains(&gossip));
+    assert!(table.remove(&gossip, DepartureReason::Timeout).is_some());
+}
+
+#[test]
+fn event_sources_do_not_lapse() {
+    let mut table = PeerTable::new();
+    let connected = peer();
+    let local = peer();
+    let now = Instant::now();
+
+    table.mark_online(connected, DiscoverySource::Connection, now + TTL);
+    table.mark_online(connected, DiscoverySource::Presence, now + TTL);
+    table.mark_online(local, DiscoverySource::Mdns, now + TTL);
+
+    assert!(table.lapsed(now + TTL * 2).is_empty());
+    assert_eq!(table.get(&connected).unwrap().sources.iter().copied().collect::<Vec<_>>(), vec![DiscoverySource::Connection]);
+    // A failed lookup does not take away the connection or mDNS
+    assert!(table.remove(&connected, DepartureReason::Timeout).is_none());
+    assert!(table.remove(&local, DepartureReason::Timeout).is_none());
+}
+
+#[test]
+fn deadline_only_moves_forward() {
+    let mut table = PeerTable::new();
+    let p = peer();
+    let now = Instant::now();
+
+    table.mark_online(p, DiscoverySource::Presence, now + TTL);
+    table.mark_online(p, DiscoverySource::Gossip, now + Duration::from_secs(10));
+    assert_eq!(table.get(&p).unwrap().online_until, now + TTL);
+
+    table.mark_online(p, DiscoverySource::Gossip, now + TTL * 2);
+    assert!(table.lapsed(now + TTL).is_empty());
+}
+
+#[test]
+fn fatal_reasons_remove_regardless_of_sources() {
+    for reason in [DepartureReason::Blocked, DepartureReason::Unresponsive, DepartureReason::Foreign] {
+        let mut table = PeerTable::new();
+        let p = peer();
+        let now = Instant::now();
+
+        table.mark_online(p, DiscoverySource::Connection, now + TTL);
+        table.mark_online(p, DiscoverySource::Mdns, now + TTL);
+        assert!(table.remove(&p, reason).is_some(), "{:?}", reason);
+        assert!(!table.contains(&p));
+    }
+}
@@ -0,0 +1,43 @@
+// Power modes: what each one still does and how often
+use p2p_core::power::PowerMode;
+
+const MODES: [PowerMode; 3] = [PowerMode::Foreground, PowerMode::Background, PowerMode::Suspended];
+
+#[test]
+fn names_round_trip() {
+    for mode in MODES {
+        assert_eq!(PowerMode::parse(mode.as_str()), Some(mode));
+    }
+    assert_eq!(PowerMode::parse("foreground"), None);
+    assert_eq!(PowerMode::parse(""), None);
+    assert_eq!(PowerMode::default(), PowerMode::Foreground);
+}
+
+#[test]
+fn lower_modes_run_less_often() {
+    for pair in MODES.windows(2) {
+        let (higher, lower) = (pair[0], pair[1]);
+        assert!(lower.discovery_interval() > higher.discovery_interval(), "{:?} vs {:?}", higher, lower);
+        assert!(lower.ping_interval() > higher.ping_interval(), "{:?} vs {:?}", higher, lower);
+        assert!(lower.heartbeat_interval() > higher.heartbeat_interval(), "{:?} vs {:?}", higher, lower);
+    }
+}
+
+#[test]
+fn foreground_keeps_the_usual_rates() {
+    let mode = PowerMode::Foreground;
+    assert_eq!(mode.discovery_interval().as_secs(), 15);
+    assert_eq!(mode.ping_interval().as_secs(), 30);
+    assert_eq!(mode.heartbeat_interval().as_secs(), 1);
+}
+
+#[test]
+fn only_foreground_discovers_and_only_suspended_drops_peers() {
+    assert!(PowerMode::Foreground.active_discovery());
+    assert!(!PowerMode::Background.active_discovery());
+    assert!(!PowerMode::Suspended.active_discovery());
+
+    assert!(PowerMode::Foreground.keeps_peers());
+    assert!(PowerMode::Background.keeps_peers());
+    assert!(!PowerMode::Suspended.keeps_peers());
+}
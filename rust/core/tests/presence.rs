@@ -0,0 +1,131 @@
+// Signed presence records and status announcements
+use std::time::{Duration, Instant};
+use libp2p::{identity::Keypair, PeerId};
+use p2p_core::network::Network;
+use p2p_core::peer_table::{DiscoverySource, PeerTable};
+use p2p_core::presence::{accept_record, presence_record, presence_record_with_ttl, verify_presence, Presence, PresenceStatus, PRESENCE_TTL};
+
+#[test]
+fn connected_peer_sees_invisible_as_offline() {
+    let keypair = Keypair::generate_ed25519();
+    let peer = PeerId::from(keypair.public());
+    let mut table = PeerTable::new();
+    table.mark_online(peer, DiscoverySource::Connection, Instant::now() + PRESENCE_TTL);
+    table.set_presence(&peer, Presence::new(PresenceStatus::Online, ""));
+    table.set_shares_last_seen(&peer, true);
+    assert_eq!(table.sharing_last_seen(), vec![peer]);
+
+    // The gossip announcement
+    let invisible = Presence::new(PresenceStatus::Invisible, "");
+    let announced = Presence::parse(&invisible.to_string()).unwrap();
+    assert!(table.set_presence(&peer, announced));
+    let entry = table.get(&peer).unwrap();
+    assert!(entry.is_invisible());
+    assert!(!entry.shows_last_seen());
+    assert!(table.sharing_last_seen().is_empty());
+
+    // Back online
+    assert!(table.set_presence(&peer, Presence::new(PresenceStatus::Online, "")));
+    assert!(!table.get(&peer).unwrap().is_invisible());
+}
+
+#[test]
+fn invisible_record_replaces_visible_one() {
+    let network = Network::default();
+    let keypair = Keypair::generate_ed25519();
+    let peer = PeerId::from(keypair.public());
+
+    let visible = presence_record(&keypair, &network, &Presence::new(PresenceStatus::Online, "hi"), true);
+    let tombstone = presence_record(&keypair, &network, &Presence::new(PresenceStatus::Invisible, ""), true);
+    // Same key, so the tombstone overwrites the visible record on other nodes
+    assert_eq!(visible.key, tombstone.key);
+
+    let verified = verify_presence(&network, &tombstone).expect("Tombstone does not verify");
+    assert_eq!(verified.peer, peer);
+    assert_eq!(verified.presence.status, PresenceStatus::Invisible);
+
+    // A peer known only from the DHT goes offline with it
+    let mut table = PeerTable::new();
+    table.mark_online(peer, DiscoverySource::Presence, Instant::now() + Duration::from_secs(10));
+    assert!(table.set_presence(&peer, verified.presence));
+    assert!(!table.get(&peer).unwrap().shows_last_seen());
+}
+
+#[test]
+fn last_seen_is_not_recorded_until_the_record_allows_it() {
+    let peer = PeerId::from(Keypair::generate_ed25519().public());
+    let mut table = PeerTable::new();
+    table.mark_online(peer, DiscoverySource::Connection, Instant::now() + PRESENCE_TTL);
+    // Known from the connection, the presence record has not arrived yet
+    assert!(!table.get(&peer).unwrap().shows_last_seen());
+    assert!(table.sharing_last_seen().is_empty());
+
+    table.set_shares_last_seen(&peer, true);
+    assert!(table.get(&peer).unwrap().shows_last_seen());
+    table.set_shares_last_seen(&peer, false);
+    assert!(table.sharing_last_seen().is_empty());
+}
+
+#[test]
+fn valid_record_verifies() {
+    let network = Network::new("staging").unwrap();
+    let keypair = Keypair::generate_ed25519();
+    let peer = PeerId::from(keypair.public());
+
+    let record = presence_record(&keypair, &network, &Presence::new(PresenceStatus::DoNotDisturb, "in a meeting"), false);
+    let verified = verify_presence(&network, &record).expect("Own record does not verify");
+    assert_eq!(verified.peer, peer);
+    assert_eq!(verified.presence, Presence::new(PresenceStatus::DoNotDisturb, "in a meeting"));
+    assert!(!verified.share_last_seen);
+    assert!(verified.expires > Instant::now() && verified.expires <= Instant::now() + PRESENCE_TTL);
+    assert!(accept_record(&network, &record));
+}
+
+#[test]
+fn forged_signature_is_rejected() {
+    let network = Network::default();
+    let keypair = Keypair::generate_ed25519();
+    let mut record = presence_record(&keypair, &network, &Presence::new(PresenceStatus::Online, ""), true);
+
+    // The signature is the last field of the envelope
+    *record.value.last_mut().unwrap() ^= 0xff;
+    assert!(verify_presence(&network, &record).is_none());
+    assert!(!accept_record(&network, &record));
+}
+
+#[test]
+fn record_under_someone_elses_key_is_rejected() {
+    let network = Network::default();
+    let signer = Keypair::generate_ed25519();
+    let victim = PeerId::from(Keypair::generate_ed25519().public());
+
+    // Signed correctly, but would keep another peer online
+    let mut record = presence_record(&signer, &network, &Presence::new(PresenceStatus::Online, ""), true);
+    record.key = network.presence_key(&victim);
+    assert!(verify_presence(&network, &record).is_none());
+    assert!(!accept_record(&network, &record));
+}
+
+#[test]
+fn record_of_another_network_is_rejected() {
+    let staging = Network::new("staging").unwrap();
+    let production = Network::new("production").unwrap();
+    let keypair = Keypair::generate_ed25519();
+
+    let record = presence_record(&keypair, &staging, &Presence::new(PresenceStatus::Online, ""), true);
+    assert!(verify_presence(&production, &record).is_none());
+    assert!(verify_presence(&Network::default(), &record).is_none());
+}
+
+#[test]
+fn lifetime_beyond_the_ttl_is_rejected() {
+    let network = Network::default();
+    let keypair = Keypair::generate_ed25519();
+    let presence = Presence::new(PresenceStatus::Online, "");
+
+    let at_limit = presence_record_with_ttl(&keypair, &network, &presence, true, PRESENCE_TTL);
+    assert!(verify_presence(&network, &at_limit).is_some());
+    let too_long = presence_record_with_ttl(&keypair, &network, &presence, true, PRESENCE_TTL + Duration::from_secs(1));
+    assert!(verify_presence(&network, &too_long).is_none());
+    assert!(!accept_record(&network, &too_long));
+}
@@ -0,0 +1,67 @@
+// Pre-shared key handshake in front of Noise, over the in-memory transport
+use std::time::Duration;
+use futures::StreamExt;
+use libp2p::{
+    core::transport::MemoryTransport,
+    pnet::PreSharedKey,
+    swarm::{dummy, DialError, SwarmEvent},
+    Multiaddr, Swarm, SwarmBuilder, TransportError,
+};
+use p2p_core::transport::{authenticate, HANDSHAKE_TIMEOUT};
+
+fn swarm(key: Option<PreSharedKey>) -> Swarm<dummy::Behaviour> {
+    SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_other_transport(|keypair| authenticate(MemoryTransport::default(), keypair, key))
+        .unwrap()
+        .with_behaviour(|_| dummy::Behaviour)
+        .unwrap()
+        .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
+        .build()
+}
+
+// Listens on `listener`, dials it from `dialer` and returns the dialer's outcome
+async fn connect(mut listener: Swarm<dummy::Behaviour>, mut dialer: Swarm<dummy::Behaviour>) -> Result<(), DialError> {
+    let addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+    listener.listen_on(addr.clone()).unwrap();
+    let wait = async {
+        loop {
+            tokio::select! {
+                event = listener.select_next_some() => if let SwarmEvent::NewListenAddr { .. } = event {
+                    dialer.dial(addr.clone()).unwrap();
+                },
+                event = dialer.select_next_some() => match event {
+                    SwarmEvent::ConnectionEstablished { .. } => return Ok(()),
+                    SwarmEvent::OutgoingConnectionError { error, .. } => return Err(error),
+                    _ => {},
+                },
+            }
+        }
+    };
+    // A mismatched handshake may only end with the handshake timeout
+    tokio::time::timeout(HANDSHAKE_TIMEOUT + Duration::from_secs(5), wait).await.expect("Timed out")
+}
+
+#[tokio::test]
+async fn same_key_connects() {
+    let key = PreSharedKey::new([7; 32]);
+    connect(swarm(Some(key)), swarm(Some(key))).await.expect("Peers with the same key did not connect");
+}
+
+#[tokio::test]
+async fn different_keys_fail_with_a_hint() {
+    let error = connect(swarm(Some(PreSharedKey::new([7; 32]))), swarm(Some(PreSharedKey::new([8; 32])))).await
+        .expect_err("Peers with different keys connected");
+    let DialError::Transport(errors) = error else {
+        panic!("Unexpected dial error: {:?}", error);
+    };
+    let TransportError::Other(e) = &errors[0].1 else {
+        panic!("Unexpected transport error: {:?}", errors[0].1);
+    };
+    assert!(e.to_string().contains("different pre-shared key"), "{}", e);
+}
+
+#[tokio::test]
+async fn keyless_peer_cannot_join() {
+    assert!(connect(swarm(Some(PreSharedKey::new([7; 32]))), swarm(None)).await.is_err());
+}
@@ -0,0 +1,153 @@
+// Redial backoff of relay and bootstrap peers
+use std::time::{Duration, Instant};
+use libp2p::{identity::Keypair, Multiaddr, PeerId};
+use p2p_core::reconnect::{Backoff, Connectivity, ReconnectSupervisor, TargetKind};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+fn addr(peer: &PeerId) -> Multiaddr {
+    format!("/ip4/192.0.2.1/tcp/4001/p2p/{}", peer).parse().unwrap()
+}
+
+// Jitter keeps every delay between half and all of the base
+fn assert_within(delay: Duration, base: Duration) {
+    assert!(delay >= base / 2 && delay <= base, "{:?} outside {:?}..={:?}", delay, base / 2, base);
+}
+
+#[test]
+fn delays_double_per_attempt() {
+    let initial = Duration::from_secs(1);
+    let mut backoff = Backoff::new(initial, Duration::from_secs(3600));
+
+    for attempt in 0..8 {
+        assert_eq!(backoff.attempt(), attempt);
+        assert_within(backoff.next_delay(), initial * 2u32.pow(attempt));
+    }
+    assert_eq!(backoff.attempt(), 8);
+}
+
+#[test]
+fn delays_stop_at_cap() {
+    let max = Duration::from_secs(120);
+    let mut backoff = Backoff::new(Duration::from_secs(1), max);
+
+    // 2^7 s is past the cap
+    for _ in 0..7 {
+        backoff.next_delay();
+    }
+    for _ in 0..100 {
+        assert_within(backoff.next_delay(), max);
+    }
+    assert_eq!(backoff.attempt(), 107);
+}
+
+#[test]
+fn reset_starts_over() {
+    let initial = Duration::from_millis(500);
+    let mut backoff = Backoff::new(initial, Duration::from_secs(60));
+
+    for _ in 0..5 {
+        backoff.next_delay();
+    }
+    backoff.reset();
+    assert_eq!(backoff.attempt(), 0);
+    assert_within(backoff.next_delay(), initial);
+}
+
+#[test]
+fn default_runs_from_one_second_to_two_minutes() {
+    let mut backoff = Backoff::default();
+
+    assert_within(backoff.next_delay(), Duration::from_secs(1));
+    for _ in 0..20 {
+        backoff.next_delay();
+    }
+    assert_within(backoff.next_delay(), Duration::from_secs(120));
+}
+
+#[test]
+fn disconnected_target_is_redialed_when_due() {
+    let mut supervisor = ReconnectSupervisor::new();
+    let relay = peer();
+    supervisor.watch(relay, addr(&relay), TargetKind::Relay);
+    supervisor.on_connected(&relay);
+    assert_eq!(supervisor.connectivity(), Connectivity::Connected);
+
+    let delay = supervisor.on_disconnected(&relay).expect("Watched peer not rescheduled");
+    assert_within(delay, Duration::from_secs(1));
+    // Already waiting, a second report does not push the redial back
+    assert!(supervisor.on_disconnected(&relay).is_none());
+    assert_eq!(supervisor.connectivity(), Connectivity::Reconnecting);
+
+    assert!(supervisor.due(Instant::now()).is_empty());
+    let due = supervisor.due(Instant::now() + Duration::from_secs(2));
+    assert_eq!(due, vec![(relay, addr(&relay), 1)]);
+    assert!(supervisor.due(Instant::now() + Duration::from_secs(2)).is_empty());
+
+    // The next failure waits longer
+    assert_within(supervisor.on_disconnected(&relay).unwrap(), Duration::from_secs(2));
+}
+
+#[test]
+fn connecting_resets_the_backoff() {
+    let mut supervisor = ReconnectSupervisor::new();
+    let bootstrap = peer();
+    supervisor.watch(bootstrap, addr(&bootstrap), TargetKind::Bootstrap);
+
+    for _ in 0..4 {
+        supervisor.on_disconnected(&bootstrap);
+        supervisor.due(Instant::now() + Duration::from_secs(3600));
+    }
+    supervisor.on_connected(&bootstrap);
+    assert_within(supervisor.on_disconnected(&bootstrap).unwrap(), Duration::from_secs(1));
+}
+
+#[test]
+fn retry_all_now_skips_pending_backoffs() {
+    let mut supervisor = ReconnectSupervisor::new();
+    let bootstrap = peer();
+    supervisor.watch(bootstrap, addr(&bootstrap), TargetKind::Bootstrap);
+    for _ in 0..6 {
+        supervisor.on_disconnected(&bootstrap);
+        supervisor.due(Instant::now() + Duration::from_secs(3600));
+    }
+    supervisor.on_disconnected(&bootstrap);
+
+    supervisor.retry_all_now();
+    assert_eq!(supervisor.due(Instant::now()).len(), 1);
+}
+
+#[test]
+fn unwatched_peers_are_ignored() {
+    let mut supervisor = ReconnectSupervisor::new();
+    let stranger = peer();
+
+    assert!(supervisor.on_disconnected(&stranger).is_none());
+    assert!(!supervisor.is_watched(&stranger));
+    assert_eq!(supervisor.connectivity(), Connectivity::Connecting);
+}
+
+#[test]
+fn relays_decide_connectivity() {
+    let mut supervisor = ReconnectSupervisor::new();
+    let relay = peer();
+    let bootstrap = peer();
+    supervisor.watch(relay, addr(&relay), TargetKind::Relay);
+    supervisor.watch(bootstrap, addr(&bootstrap), TargetKind::Bootstrap);
+    assert_eq!(supervisor.connectivity(), Connectivity::Connecting);
+
+    supervisor.on_connected(&bootstrap);
+    assert_eq!(supervisor.connectivity(), Connectivity::Connecting);
+
+    supervisor.on_disconnected(&relay);
+    assert_eq!(supervisor.connectivity(), Connectivity::Reconnecting);
+
+    supervisor.on_connected(&relay);
+    assert_eq!(supervisor.connectivity(), Connectivity::Connected);
+
+    // A peer that is both keeps the relay role
+    supervisor.watch(relay, addr(&relay), TargetKind::Bootstrap);
+    assert_eq!(supervisor.kind(&relay), Some(TargetKind::Relay));
+}
@@ -0,0 +1,193 @@
+// DHT record store: persistence across restarts, lifetimes and size limits
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use libp2p::{
+    identity::Keypair,
+    kad::{store::{Error, RecordStore}, ProviderRecord, Record, RecordKey},
+    Multiaddr, PeerId,
+};
+use p2p_core::record_store::{DhtStore, DhtStoreConfig};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+// Removed again when dropped
+struct StoreFile(PathBuf);
+
+impl StoreFile {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("dht_store_{}.redb", peer())))
+    }
+}
+
+impl Drop for StoreFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn record(key: &str, value: &[u8]) -> Record {
+    Record::new(RecordKey::new(&key), value.to_vec())
+}
+
+#[test]
+fn records_survive_restart() {
+    let file = StoreFile::new();
+    let local = peer();
+    let publisher = peer();
+
+    let mut store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    assert!(store.is_persistent());
+    let mut kept = record("kept", b"hello");
+    kept.publisher = Some(publisher);
+    store.put(kept).unwrap();
+    store.put(record("removed", b"gone")).unwrap();
+    store.remove(&RecordKey::new(&"removed"));
+    drop(store);
+
+    let store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    let restored = store.get(&RecordKey::new(&"kept")).expect("Record not restored");
+    assert_eq!(restored.value, b"hello");
+    assert_eq!(restored.publisher, Some(publisher));
+    assert!(restored.expires.is_some());
+    assert!(store.get(&RecordKey::new(&"removed")).is_none());
+    assert_eq!(store.records().count(), 1);
+}
+
+#[test]
+fn providers_survive_restart() {
+    let file = StoreFile::new();
+    let local = peer();
+    let provider = peer();
+    let key = RecordKey::new(&"room");
+    let addr: Multiaddr = "/ip4/192.0.2.1/tcp/4001".parse().unwrap();
+
+    let mut store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    store.add_provider(ProviderRecord::new(key.clone(), provider, vec![addr.clone()])).unwrap();
+    store.add_provider(ProviderRecord::new(key.clone(), local, Vec::new())).unwrap();
+    store.remove_provider(&key, &local);
+    drop(store);
+
+    let store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    let providers = store.providers(&key);
+    assert_eq!(providers.len(), 1);
+    assert_eq!(providers[0].provider, provider);
+    assert_eq!(providers[0].addresses, vec![addr]);
+    assert_eq!(store.provided().count(), 0);
+}
+
+#[test]
+fn lifetimes_are_capped() {
+    let max_ttl = Duration::from_secs(60);
+    let config = DhtStoreConfig { max_ttl, ..DhtStoreConfig::default() };
+    let mut store = DhtStore::in_memory(peer(), config);
+    assert!(!store.is_persistent());
+
+    // Without expiry
+    store.put(record("forever", b"x")).unwrap();
+    // Beyond the cap
+    let mut late = record("late", b"x");
+    late.expires = Some(Instant::now() + Duration::from_secs(3600));
+    store.put(late).unwrap();
+    // Within the cap, kept as is
+    let soon = Instant::now() + Duration::from_secs(10);
+    let mut early = record("early", b"x");
+    early.expires = Some(soon);
+    store.put(early).unwrap();
+
+    let latest = Instant::now() + max_ttl;
+    for key in ["forever", "late"] {
+        let expires = store.get(&RecordKey::new(&key)).unwrap().expires.unwrap();
+        assert!(expires <= latest, "{} not capped", key);
+        assert!(expires > Instant::now() + Duration::from_secs(50), "{} capped too short", key);
+    }
+    assert_eq!(store.get(&RecordKey::new(&"early")).unwrap().expires, Some(soon));
+
+    let key = RecordKey::new(&"room");
+    store.add_provider(ProviderRecord::new(key.clone(), peer(), Vec::new())).unwrap();
+    assert!(store.providers(&key)[0].expires.unwrap() <= Instant::now() + max_ttl);
+}
+
+#[test]
+fn expired_entries_are_dropped_on_load() {
+    let file = StoreFile::new();
+    let local = peer();
+    let key = RecordKey::new(&"room");
+
+    let mut store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    let mut short = record("short", b"x");
+    short.expires = Some(Instant::now() + Duration::from_millis(50));
+    store.put(short).unwrap();
+    store.put(record("long", b"x")).unwrap();
+    let mut provider = ProviderRecord::new(key.clone(), peer(), Vec::new());
+    provider.expires = Some(Instant::now() + Duration::from_millis(50));
+    store.add_provider(provider).unwrap();
+    drop(store);
+
+    std::thread::sleep(Duration::from_millis(100));
+
+    let store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    assert!(store.get(&RecordKey::new(&"short")).is_none());
+    assert!(store.get(&RecordKey::new(&"long")).is_some());
+    assert!(store.providers(&key).is_empty());
+    drop(store);
+
+    // Deleted from the file too, not just skipped
+    let store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    assert_eq!(store.records().count(), 1);
+}
+
+#[test]
+fn size_limits_apply() {
+    let config = DhtStoreConfig {
+        max_records: 2,
+        max_value_bytes: 4,
+        max_providers_per_key: 2,
+        max_provided_keys: 1,
+        ..DhtStoreConfig::default()
+    };
+    let local = peer();
+    let mut store = DhtStore::in_memory(local, config);
+
+    assert!(matches!(store.put(record("big", b"too long")), Err(Error::ValueTooLarge)));
+    store.put(record("a", b"x")).unwrap();
+    store.put(record("b", b"x")).unwrap();
+    assert!(matches!(store.put(record("c", b"x")), Err(Error::MaxRecords)));
+    // Replacing an existing record is not a new one
+    store.put(record("a", b"y")).unwrap();
+
+    let key = RecordKey::new(&"room");
+    for _ in 0..3 {
+        store.add_provider(ProviderRecord::new(key.clone(), peer(), Vec::new())).unwrap();
+    }
+    assert_eq!(store.providers(&key).len(), 2);
+
+    // Counts every key with providers, not only our own
+    let other = ProviderRecord::new(RecordKey::new(&"other"), local, Vec::new());
+    assert!(matches!(store.add_provider(other), Err(Error::MaxProvidedKeys)));
+}
+
+#[test]
+fn saved_changes_are_overwritten_by_later_ones() {
+    let file = StoreFile::new();
+    let local = peer();
+    let key = RecordKey::new(&"room");
+
+    let mut store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    store.put(record("a", b"x")).unwrap();
+    store.put(record("b", b"x")).unwrap();
+    store.add_provider(ProviderRecord::new(key.clone(), peer(), Vec::new())).unwrap();
+    store.save_if_dirty();
+    // Changed again after the save
+    store.remove(&RecordKey::new(&"a"));
+    store.put(record("b", b"y")).unwrap();
+    let provider = store.providers(&key)[0].provider;
+    store.remove_provider(&key, &provider);
+    drop(store);
+
+    let store = DhtStore::persistent(local, &file.0, DhtStoreConfig::default());
+    assert!(store.get(&RecordKey::new(&"a")).is_none());
+    assert_eq!(store.get(&RecordKey::new(&"b")).unwrap().value, b"y");
+    assert!(store.providers(&key).is_empty());
+}
@@ -0,0 +1,99 @@
+// Rendezvous bookkeeping: renewal at half the TTL, retries and forgotten points
+use std::time::{Duration, Instant};
+use libp2p::{identity::Keypair, rendezvous::{Cookie, Namespace}, PeerId};
+use p2p_core::rendezvous::{RendezvousState, MIN_REGISTRATION_TTL, REGISTRATION_TTL};
+
+fn peer() -> PeerId {
+    Keypair::generate_ed25519().public().to_peer_id()
+}
+
+fn namespace(name: &str) -> Namespace {
+    Namespace::new(name.to_string()).unwrap()
+}
+
+fn secs(n: u64) -> Duration {
+    Duration::from_secs(n)
+}
+
+#[test]
+fn registration_is_renewed_at_half_the_ttl() {
+    let global = namespace("p2p-chat");
+    let mut state = RendezvousState::new(global.clone());
+    let point = peer();
+
+    let start = Instant::now();
+    assert_eq!(state.due_registrations(point, start), vec![global.clone()]);
+    // In flight, not sent twice
+    assert!(state.due_registrations(point, start).is_empty());
+
+    state.on_registered(point, global.clone(), REGISTRATION_TTL);
+    let half = secs(REGISTRATION_TTL / 2);
+    assert!(state.due_registrations(point, start + half - secs(1)).is_empty());
+    assert_eq!(state.due_registrations(point, Instant::now() + half), vec![global]);
+}
+
+#[test]
+fn short_ttls_wait_at_least_the_retry_delay() {
+    let global = namespace("p2p-chat");
+    let mut state = RendezvousState::new(global.clone());
+    let point = peer();
+
+    state.due_registrations(point, Instant::now());
+    state.on_registered(point, global.clone(), MIN_REGISTRATION_TTL);
+    assert!(state.due_registrations(point, Instant::now() + secs(MIN_REGISTRATION_TTL / 2)).is_empty());
+    assert_eq!(state.due_registrations(point, Instant::now() + secs(MIN_REGISTRATION_TTL)), vec![global]);
+}
+
+#[test]
+fn failed_registration_is_retried_after_a_minute() {
+    let global = namespace("p2p-chat");
+    let mut state = RendezvousState::new(global.clone());
+    let point = peer();
+
+    state.due_registrations(point, Instant::now());
+    state.on_register_failed(point, global.clone());
+    assert!(state.due_registrations(point, Instant::now() + secs(30)).is_empty());
+    assert_eq!(state.due_registrations(point, Instant::now() + secs(60)), vec![global]);
+}
+
+#[test]
+fn forgotten_point_starts_from_scratch() {
+    let global = namespace("p2p-chat");
+    let mut state = RendezvousState::new(global.clone());
+    let (point, other) = (peer(), peer());
+
+    for p in [point, other] {
+        state.due_registrations(p, Instant::now());
+        state.on_registered(p, global.clone(), REGISTRATION_TTL);
+        state.set_cookie(p, Cookie::for_namespace(global.clone()));
+    }
+
+    state.forget_point(&point);
+    assert_eq!(state.due_registrations(point, Instant::now()), vec![global.clone()]);
+    assert!(state.cookie(point, &global).is_none());
+    // Other points are left alone
+    assert!(state.due_registrations(other, Instant::now()).is_empty());
+    assert!(state.cookie(other, &global).is_some());
+}
+
+#[test]
+fn rooms_register_alongside_the_network() {
+    let global = namespace("p2p-chat");
+    let lobby = namespace("p2p-chat/room/lobby");
+    let mut state = RendezvousState::new(global.clone());
+    let (point, member) = (peer(), peer());
+
+    assert!(state.join_room(lobby.clone(), "lobby".to_string()));
+    let mut due = state.due_registrations(point, Instant::now());
+    due.sort_by_key(|ns| ns.to_string());
+    assert_eq!(due, vec![global.clone(), lobby.clone()]);
+    assert_eq!(state.room(&lobby), Some("lobby"));
+    assert!(state.add_member(&lobby, member));
+    assert!(!state.add_member(&lobby, member));
+    // Not a room
+    assert!(!state.add_member(&global, member));
+
+    assert_eq!(state.leave_room("lobby"), Some(lobby.clone()));
+    assert!(state.leave_room("lobby").is_none());
+    assert_eq!(state.namespaces(), vec![global]);
+}
@@ -0,0 +1,106 @@
+// Swapping gossipsub while connections are open
+use std::time::Duration;
+use futures::StreamExt;
+use libp2p::{
+    core::{transport::MemoryTransport, upgrade::Version},
+    gossipsub, identity::Keypair, noise,
+    swarm::{dial_opts::{DialOpts, PeerCondition}, NetworkBehaviour, SwarmEvent},
+    yamux, Multiaddr, Swarm, SwarmBuilder, Transport,
+};
+use p2p_core::behaviour::Replaceable;
+
+#[derive(NetworkBehaviour)]
+struct Chat {
+    gossipsub: Replaceable<gossipsub::Behaviour>,
+}
+
+fn gossipsub(keypair: &Keypair, heartbeat: Duration) -> gossipsub::Behaviour {
+    let config = gossipsub::ConfigBuilder::default().heartbeat_interval(heartbeat).build().unwrap();
+    gossipsub::Behaviour::new(gossipsub::MessageAuthenticity::Signed(keypair.clone()), config).unwrap()
+}
+
+fn swarm() -> Swarm<Chat> {
+    SwarmBuilder::with_new_identity()
+        .with_tokio()
+        .with_other_transport(|keypair| {
+            MemoryTransport::default()
+                .upgrade(Version::V1)
+                .authenticate(noise::Config::new(keypair).unwrap())
+                .multiplex(yamux::Config::default())
+        })
+        .unwrap()
+        .with_behaviour(|keypair| Chat {
+            gossipsub: Replaceable::new(gossipsub(keypair, Duration::from_millis(100))),
+        })
+        .unwrap()
+        .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
+        .build()
+}
+
+// Drives both swarms until `done` returns true for an event of `a`
+async fn run_until(a: &mut Swarm<Chat>, b: &mut Swarm<Chat>, mut done: impl FnMut(&SwarmEvent<ChatEvent>) -> bool) {
+    let wait = async {
+        loop {
+            tokio::select! {
+                event = a.select_next_some() => if done(&event) { return },
+                _ = b.select_next_some() => {},
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), wait).await.expect("Timed out");
+}
+
+#[tokio::test]
+async fn replaced_gossipsub_takes_over_reopened_connections() {
+    let topic = gossipsub::IdentTopic::new("chat");
+    let mut a = swarm();
+    let mut b = swarm();
+    let a_id = *a.local_peer_id();
+    let b_id = *b.local_peer_id();
+    a.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
+    b.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
+
+    let addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+    a.listen_on(addr.clone()).unwrap();
+    run_until(&mut a, &mut b, |e| matches!(e, SwarmEvent::NewListenAddr { .. })).await;
+    b.dial(addr.clone()).unwrap();
+    let mut old = None;
+    run_until(&mut a, &mut b, |e| match e {
+        SwarmEvent::ConnectionEstablished { connection_id, .. } => {
+            old = Some(*connection_id);
+            true
+        },
+        _ => false,
+    }).await;
+
+    // New behaviour, then a second connection before the old one is closed
+    let keypair = Keypair::generate_ed25519();
+    a.behaviour_mut().gossipsub.replace(gossipsub(&keypair, Duration::from_millis(200)));
+    a.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
+    b.dial(DialOpts::peer_id(a_id).addresses(vec![addr]).condition(PeerCondition::Always).build()).unwrap();
+    run_until(&mut a, &mut b, |e| matches!(e, SwarmEvent::ConnectionEstablished { num_established, .. } if num_established.get() == 2)).await;
+    assert!(a.close_connection(old.unwrap()));
+    run_until(&mut a, &mut b, |e| matches!(e, SwarmEvent::ConnectionClosed { .. })).await;
+    assert!(a.is_connected(&b_id));
+
+    // The new behaviour knows the reopened connection
+    let mut interval = tokio::time::interval(Duration::from_millis(100));
+    let wait = async {
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let _ = b.behaviour_mut().gossipsub.publish(topic.clone(), b"hello".to_vec());
+                },
+                event = a.select_next_some() => {
+                    if let SwarmEvent::Behaviour(ChatEvent::Gossipsub(gossipsub::Event::Message { message, .. })) = event {
+                        assert_eq!(message.data, b"hello");
+                        assert_eq!(message.source, Some(b_id));
+                        return;
+                    }
+                },
+                _ = b.select_next_some() => {},
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), wait).await.expect("No message through the new behaviour");
+}
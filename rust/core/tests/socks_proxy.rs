@@ -0,0 +1,235 @@
+// SOCKS5 outbound transport against a local SOCKS stand-in
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures::StreamExt;
+use libp2p::{
+    identity::Keypair, ping, swarm::SwarmEvent, Multiaddr, PeerId, Swarm, SwarmBuilder,
+};
+use p2p_core::socks::Socks5Proxy;
+use p2p_core::transport::{build_transport, TransportConfig};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// Destinations requested through the stand-in, as "host:port"
+type Requests = Arc<Mutex<Vec<String>>>;
+
+// Minimal SOCKS5 server. Domain names always point to 127.0.0.1, so reaching a peer
+// through an unresolvable name proves the name was resolved by the proxy
+async fn spawn_proxy(credentials: Option<(&'static str, &'static str)>) -> (SocketAddr, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests: Requests = Arc::new(Mutex::new(Vec::new()));
+
+    let recorded = requests.clone();
+    tokio::spawn(async move {
+        while let Ok((client, _)) = listener.accept().await {
+            tokio::spawn(serve_client(client, credentials, recorded.clone()));
+        }
+    });
+    (addr, requests)
+}
+
+async fn serve_client(mut client: TcpStream, credentials: Option<(&str, &str)>, requests: Requests) -> std::io::Result<()> {
+    // Greeting
+    let mut head = [0u8; 2];
+    client.read_exact(&mut head).await?;
+    let mut methods = vec![0u8; head[1] as usize];
+    client.read_exact(&mut methods).await?;
+    let wanted = if credentials.is_some() { 2 } else { 0 };
+    if !methods.contains(&wanted) {
+        client.write_all(&[5, 0xff]).await?;
+        return Ok(());
+    }
+    client.write_all(&[5, wanted]).await?;
+
+    // Username/password sub-negotiation
+    if let Some((user, password)) = credentials {
+        let mut ver_len = [0u8; 2];
+        client.read_exact(&mut ver_len).await?;
+        let mut got_user = vec![0u8; ver_len[1] as usize];
+        client.read_exact(&mut got_user).await?;
+        let got_password_len = client.read_u8().await?;
+        let mut got_password = vec![0u8; got_password_len as usize];
+        client.read_exact(&mut got_password).await?;
+        let ok = got_user == user.as_bytes() && got_password == password.as_bytes();
+        client.write_all(&[1, if ok { 0 } else { 1 }]).await?;
+        if !ok {
+            return Ok(());
+        }
+    }
+
+    // CONNECT
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip).await?;
+            Ipv4Addr::from(ip).to_string()
+        },
+        3 => {
+            let len = client.read_u8().await?;
+            let mut name = vec![0u8; len as usize];
+            client.read_exact(&mut name).await?;
+            String::from_utf8(name).unwrap()
+        },
+        _ => {
+            client.write_all(&[5, 8, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+            return Ok(());
+        },
+    };
+    let port = client.read_u16().await?;
+    requests.lock().unwrap().push(format!("{host}:{port}"));
+
+    let Ok(mut upstream) = TcpStream::connect(("127.0.0.1", port)).await else {
+        client.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]).await?;
+        return Ok(());
+    };
+    client.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+    Ok(())
+}
+
+fn swarm(config: TransportConfig) -> Swarm<ping::Behaviour> {
+    let keypair = Keypair::generate_ed25519();
+    let (transport, _) = build_transport(&keypair, keypair.public().to_peer_id(), &config);
+    SwarmBuilder::with_existing_identity(keypair)
+        .with_tokio()
+        .with_other_transport(|_| transport).unwrap()
+        .with_behaviour(|_| ping::Behaviour::default()).unwrap()
+        .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(10)))
+        .build()
+}
+
+// Plain peer listening on loopback, returns its TCP port
+async fn spawn_peer() -> (PeerId, u16) {
+    let mut peer = swarm(TransportConfig::default());
+    peer.listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap()).unwrap();
+    let port = loop {
+        if let SwarmEvent::NewListenAddr { address, .. } = peer.select_next_some().await {
+            break address.iter().find_map(|p| match p {
+                libp2p::multiaddr::Protocol::Tcp(port) => Some(port),
+                _ => None,
+            }).unwrap();
+        }
+    };
+    let peer_id = *peer.local_peer_id();
+    tokio::spawn(async move {
+        loop {
+            peer.select_next_some().await;
+        }
+    });
+    (peer_id, port)
+}
+
+// Dial and wait for the outcome: Ok on connection, Err with the dial error otherwise
+async fn dial(client: &mut Swarm<ping::Behaviour>, addr: Multiaddr) -> Result<(), String> {
+    client.dial(addr).map_err(|e| e.to_string())?;
+    let outcome = async {
+        loop {
+            match client.select_next_some().await {
+                SwarmEvent::ConnectionEstablished { .. } => return Ok(()),
+                SwarmEvent::OutgoingConnectionError { error, .. } => return Err(error.to_string()),
+                _ => {},
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(10), outcome).await.expect("dial timed out")
+}
+
+fn proxy_config(proxy: Socks5Proxy) -> TransportConfig {
+    TransportConfig::default().with_socks5_proxy(Some(proxy), true)
+}
+
+#[tokio::test]
+async fn dials_through_proxy() {
+    let (proxy_addr, requests) = spawn_proxy(None).await;
+    let (peer_id, port) = spawn_peer().await;
+
+    let mut client = swarm(proxy_config(Socks5Proxy::new(proxy_addr)));
+    dial(&mut client, format!("/ip4/127.0.0.1/tcp/{port}/p2p/{peer_id}").parse().unwrap()).await.unwrap();
+
+    assert_eq!(requests.lock().unwrap().as_slice(), [format!("127.0.0.1:{port}")]);
+}
+
+#[tokio::test]
+async fn proxy_resolves_host_names() {
+    let (proxy_addr, requests) = spawn_proxy(None).await;
+    let (peer_id, port) = spawn_peer().await;
+
+    // .invalid never resolves locally
+    let mut client = swarm(proxy_config(Socks5Proxy::new(proxy_addr)));
+    dial(&mut client, format!("/dns4/peer.invalid/tcp/{port}/p2p/{peer_id}").parse().unwrap()).await.unwrap();
+
+    assert_eq!(requests.lock().unwrap().as_slice(), [format!("peer.invalid:{port}")]);
+}
+
+#[tokio::test]
+async fn websocket_goes_through_proxy() {
+    let (proxy_addr, requests) = spawn_proxy(None).await;
+
+    let mut peer = swarm(TransportConfig::default());
+    peer.listen_on("/ip4/127.0.0.1/tcp/0/ws".parse().unwrap()).unwrap();
+    let listen_addr = loop {
+        if let SwarmEvent::NewListenAddr { address, .. } = peer.select_next_some().await {
+            break address;
+        }
+    };
+    let peer_id = *peer.local_peer_id();
+    tokio::spawn(async move {
+        loop {
+            peer.select_next_some().await;
+        }
+    });
+
+    let mut client = swarm(proxy_config(Socks5Proxy::new(proxy_addr)));
+    dial(&mut client, listen_addr.with(libp2p::multiaddr::Protocol::P2p(peer_id))).await.unwrap();
+    assert_eq!(requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn authenticates_with_credentials() {
+    let (proxy_addr, requests) = spawn_proxy(Some(("alice", "secret"))).await;
+    let (peer_id, port) = spawn_peer().await;
+    let target: Multiaddr = format!("/ip4/127.0.0.1/tcp/{port}/p2p/{peer_id}").parse().unwrap();
+
+    let wrong = Socks5Proxy::parse(&format!("alice:guess@{proxy_addr}")).unwrap();
+    let error = dial(&mut swarm(proxy_config(wrong)), target.clone()).await.unwrap_err();
+    assert!(error.contains("authentication failed"), "{error}");
+    assert!(requests.lock().unwrap().is_empty());
+
+    let right = Socks5Proxy::parse(&format!("alice:secret@{proxy_addr}")).unwrap();
+    dial(&mut swarm(proxy_config(right)), target).await.unwrap();
+    assert_eq!(requests.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn proxy_only_never_listens_or_dials_directly() {
+    let (proxy_addr, requests) = spawn_proxy(None).await;
+    let config = proxy_config(Socks5Proxy::new(proxy_addr));
+    assert!(!config.direct_enabled());
+    assert!(!config.udp_transports_enabled());
+
+    let mut client = swarm(config);
+    assert!(client.listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap()).is_err());
+    assert!(client.listen_on("/ip4/127.0.0.1/udp/0/quic-v1".parse().unwrap()).is_err());
+
+    // QUIC cannot be proxied, so it is not dialed at all
+    let peer_id = Keypair::generate_ed25519().public().to_peer_id();
+    assert!(dial(&mut client, format!("/ip4/127.0.0.1/udp/4001/quic-v1/p2p/{peer_id}").parse().unwrap()).await.is_err());
+    assert!(requests.lock().unwrap().is_empty());
+}
+
+#[test]
+fn parses_proxy_settings() {
+    let proxy = Socks5Proxy::parse("127.0.0.1:9050").unwrap();
+    assert_eq!(proxy.addr, "127.0.0.1:9050".parse().unwrap());
+    assert_eq!(proxy.credentials, None);
+
+    let proxy = Socks5Proxy::parse("user:pa:ss@127.0.0.1:9150").unwrap();
+    assert_eq!(proxy.credentials, Some(("user".to_string(), "pa:ss".to_string())));
+
+    assert!(Socks5Proxy::parse("127.0.0.1").is_err());
+    assert!(Socks5Proxy::parse("user@127.0.0.1:9050").is_err());
+}
@@ -0,0 +1,263 @@
+// UPnP port mapping against a local IGD stand-in (SSDP responder + SOAP control endpoint).
+// libp2p searches the real multicast group, so a router on the LAN could answer and get the mapping.
+// The gateway tests only run when asked for: cargo test --test upnp_gateway -- --ignored
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures::future::poll_fn;
+use libp2p::{
+    core::transport::ListenerId,
+    swarm::{behaviour::FromSwarm, NetworkBehaviour, NewListenAddr, ToSwarm},
+    upnp, Multiaddr,
+};
+use p2p_core::port_mapping::PortMapping;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+// Only one stand-in answers the searches at a time
+static GATEWAY_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+// libp2p only searches the well-known SSDP port
+const SSDP_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 1900));
+
+// Shares the port with other SSDP listeners on the host (they set the same options)
+fn bind_ssdp(addr: SocketAddr) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    let socket = UdpSocket::from_std(socket.into())?;
+    socket.join_multicast_v4(Ipv4Addr::new(239, 255, 255, 250), Ipv4Addr::UNSPECIFIED)?;
+    Ok(socket)
+}
+
+const ROOT_DESC: &str = r#"<?xml version="1.0"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0">
+<device>
+<deviceType>urn:schemas-upnp-org:device:InternetGatewayDevice:1</deviceType>
+<serviceList>
+<service>
+<serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>
+<serviceId>urn:upnp-org:serviceId:WANIPConn1</serviceId>
+<controlURL>/ctl/IPConn</controlURL>
+<eventSubURL>/evt/IPConn</eventSubURL>
+<SCPDURL>/WANIPCn.xml</SCPDURL>
+</service>
+</serviceList>
+</device>
+</root>"#;
+
+const SCPD: &str = r#"<?xml version="1.0"?>
+<scpd xmlns="urn:schemas-upnp-org:service-1-0">
+<actionList>
+<action><name>AddPortMapping</name><argumentList>
+<argument><name>NewRemoteHost</name><direction>in</direction></argument>
+<argument><name>NewExternalPort</name><direction>in</direction></argument>
+<argument><name>NewProtocol</name><direction>in</direction></argument>
+<argument><name>NewInternalPort</name><direction>in</direction></argument>
+<argument><name>NewInternalClient</name><direction>in</direction></argument>
+<argument><name>NewEnabled</name><direction>in</direction></argument>
+<argument><name>NewPortMappingDescription</name><direction>in</direction></argument>
+<argument><name>NewLeaseDuration</name><direction>in</direction></argument>
+</argumentList></action>
+<action><name>DeletePortMapping</name><argumentList>
+<argument><name>NewRemoteHost</name><direction>in</direction></argument>
+<argument><name>NewExternalPort</name><direction>in</direction></argument>
+<argument><name>NewProtocol</name><direction>in</direction></argument>
+</argumentList></action>
+<action><name>GetExternalIPAddress</name><argumentList>
+<argument><name>NewExternalIPAddress</name><direction>out</direction></argument>
+</argumentList></action>
+</actionList>
+</scpd>"#;
+
+fn soap_response(action: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+<s:Body><u:{action}Response xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1">{body}</u:{action}Response></s:Body>
+</s:Envelope>"#
+    )
+}
+
+// Fake Internet Gateway Device answering SSDP on `ssdp_addr`. Returns the SOAP actions it received
+async fn spawn_gateway(ssdp_addr: SocketAddr, external_ip: Ipv4Addr) -> Arc<Mutex<Vec<String>>> {
+    let actions = Arc::new(Mutex::new(Vec::new()));
+
+    // SSDP: answer every M-SEARCH with our description URL
+    let ssdp = bind_ssdp(ssdp_addr).unwrap_or_else(|e| panic!("Cannot bind SSDP on {ssdp_addr}: {e}"));
+
+    let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_port = http.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        while let Ok((n, from)) = ssdp.recv_from(&mut buf).await {
+            if !String::from_utf8_lossy(&buf[..n]).starts_with("M-SEARCH") {
+                continue;
+            }
+            let reply = format!(
+                "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nUSN: uuid:p2p-msg-igd::urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\nLOCATION: http://127.0.0.1:{http_port}/rootDesc.xml\r\n\r\n"
+            );
+            let _ = ssdp.send_to(reply.as_bytes(), from).await;
+        }
+    });
+
+    let recorded = actions.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = http.accept().await {
+            tokio::spawn(serve_http(stream, external_ip, recorded.clone()));
+        }
+    });
+
+    actions
+}
+
+async fn serve_http(mut stream: TcpStream, external_ip: Ipv4Addr, actions: Arc<Mutex<Vec<String>>>) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    // Read headers, then the body announced by Content-Length
+    let (head, body_len) = loop {
+        let Ok(n) = stream.read(&mut buf).await else { return };
+        if n == 0 {
+            return;
+        }
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request).to_string();
+        if let Some(end) = text.find("\r\n\r\n") {
+            let content_length = text[..end].lines()
+                .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                .unwrap_or(0);
+            break (text[..end].to_string(), end + 4 + content_length);
+        }
+    };
+    while request.len() < body_len {
+        let Ok(n) = stream.read(&mut buf).await else { return };
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+    let soap_action = head.lines()
+        .find_map(|l| l.to_ascii_lowercase().starts_with("soapaction:").then(|| l.to_string()))
+        .and_then(|l| l.rsplit('#').next().map(|a| a.trim_matches(|c| c == '"' || c == ' ').to_string()));
+
+    let body = match (path.as_str(), soap_action.as_deref()) {
+        ("/rootDesc.xml", _) => ROOT_DESC.to_string(),
+        ("/WANIPCn.xml", _) => SCPD.to_string(),
+        ("/ctl/IPConn", Some("GetExternalIPAddress")) => {
+            soap_response("GetExternalIPAddress", &format!("<NewExternalIPAddress>{external_ip}</NewExternalIPAddress>"))
+        },
+        ("/ctl/IPConn", Some(action)) => {
+            actions.lock().unwrap().push(action.to_string());
+            soap_response(action, "")
+        },
+        _ => String::new(),
+    };
+
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+// Announce a LAN listen address to the behaviour and wait for its first event
+async fn next_upnp_event(behaviour: &mut upnp::tokio::Behaviour) -> upnp::Event {
+    let listen_addr: Multiaddr = "/ip4/192.168.1.50/tcp/4001".parse().unwrap();
+    behaviour.on_swarm_event(FromSwarm::NewListenAddr(NewListenAddr {
+        listener_id: ListenerId::next(),
+        addr: &listen_addr,
+    }));
+
+    let wait = poll_fn(|cx| loop {
+        match behaviour.poll(cx) {
+            std::task::Poll::Ready(ToSwarm::GenerateEvent(event)) => return std::task::Poll::Ready(event),
+            std::task::Poll::Ready(_) => continue,
+            std::task::Poll::Pending => return std::task::Poll::Pending,
+        }
+    });
+    tokio::time::timeout(Duration::from_secs(15), wait).await.expect("no UPnP event from the stand-in")
+}
+
+#[tokio::test]
+#[ignore = "sends an SSDP search to the LAN, run with --ignored"]
+async fn maps_port_and_skips_relay() {
+    let _lock = GATEWAY_LOCK.lock().await;
+    let actions = spawn_gateway(SSDP_ADDR, Ipv4Addr::new(81, 2, 69, 160)).await;
+
+    let mut behaviour = upnp::tokio::Behaviour::default();
+    let event = next_upnp_event(&mut behaviour).await;
+
+    let expected: Multiaddr = "/ip4/81.2.69.160/tcp/4001".parse().unwrap();
+    assert!(matches!(&event, upnp::Event::NewExternalAddr(addr) if *addr == expected), "{event:?}");
+    assert_eq!(actions.lock().unwrap().as_slice(), ["AddPortMapping"]);
+
+    // The relay is held while searching, a mapping releases it
+    let mut mapping = PortMapping::new(true);
+    assert!(mapping.needs_relay());
+    assert!(mapping.on_event(&event));
+    assert!(!mapping.needs_relay());
+    assert_eq!(mapping.as_str(), "MAPPED");
+
+    // Losing the only mapping falls back to the relay
+    assert!(mapping.on_event(&upnp::Event::ExpiredExternalAddr(expected)));
+    assert!(mapping.needs_relay());
+}
+
+#[tokio::test]
+#[ignore = "sends an SSDP search to the LAN, run with --ignored"]
+async fn private_gateway_falls_back_to_relay() {
+    let _lock = GATEWAY_LOCK.lock().await;
+    // Double NAT: the gateway itself only has a private address
+    let actions = spawn_gateway(SSDP_ADDR, Ipv4Addr::new(10, 0, 0, 1)).await;
+
+    let mut behaviour = upnp::tokio::Behaviour::default();
+    let event = next_upnp_event(&mut behaviour).await;
+
+    assert!(matches!(event, upnp::Event::NonRoutableGateway), "{event:?}");
+    assert!(actions.lock().unwrap().is_empty());
+
+    let mut mapping = PortMapping::new(true);
+    assert!(!mapping.on_event(&event));
+    assert!(mapping.needs_relay());
+    assert_eq!(mapping.as_str(), "FAILED");
+}
+
+#[test]
+fn disabled_mapping_needs_relay() {
+    let mapping = PortMapping::new(false);
+    assert!(mapping.needs_relay());
+    assert_eq!(mapping.as_str(), "DISABLED");
+}
+
+#[test]
+fn searching_needs_relay() {
+    // A gateway that refuses the mapping sends no event, the search never ends
+    let mapping = PortMapping::new(true);
+    assert!(mapping.needs_relay());
+    assert_eq!(mapping.as_str(), "SEARCHING");
+}
+
+#[test]
+fn mapping_releases_relay_until_it_expires() {
+    let first: Multiaddr = "/ip4/81.2.69.160/tcp/4001".parse().unwrap();
+    let second: Multiaddr = "/ip4/81.2.69.160/udp/4001/quic-v1".parse().unwrap();
+    let mut mapping = PortMapping::new(true);
+
+    assert!(mapping.on_event(&upnp::Event::NewExternalAddr(first.clone())));
+    assert!(!mapping.on_event(&upnp::Event::NewExternalAddr(second.clone())));
+    assert!(!mapping.needs_relay());
+
+    // One mapping left is enough
+    assert!(!mapping.on_event(&upnp::Event::ExpiredExternalAddr(first)));
+    assert!(mapping.on_event(&upnp::Event::ExpiredExternalAddr(second)));
+    assert_eq!(mapping.as_str(), "FAILED");
+}
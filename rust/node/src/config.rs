@@ -6,4 +6,23 @@ pub struct AppConfig {
     pub relay_address: String,
     pub bootstrap_nodes: Vec<String>,
     pub listen_port: u16,
+    pub enable_upnp: bool,
+    // Private network key (swarm.key contents or 64 hex chars), empty for <storage_path>/swarm.key
+    pub pre_shared_key: String,
+    // Namespace for topics and protocols, empty for the default network
+    pub network_id: String,
+    // Outbound SOCKS5 proxy ("host:port" or "user:password@host:port"), empty for none
+    pub socks5_proxy: String,
+    // Only connect through the proxy: no listening, mDNS, hole punching or UPnP
+    pub proxy_only: bool,
+    // Connection caps, 0 keeps the built-in default
+    pub max_connections: u16,
+    pub max_connections_per_peer: u16,
+    // Keep DHT records on disk across restarts instead of only in memory
+    pub persist_dht: bool,
+    // Do not let other peers record when we were last seen
+    pub hide_last_seen: bool,
+    // Public resolvers used where the OS DNS configuration is unreadable (Android):
+    // "cloudflare", "google", "quad9" or "none", empty for cloudflare
+    pub dns_fallback: String,
 }
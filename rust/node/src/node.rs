@@ -1,37 +1,323 @@
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::frb_generated::StreamSink;
 use futures::StreamExt;
 use flutter_rust_bridge::frb;
 use libp2p::{
     gossipsub,
     kad::{
-        store::MemoryStore,
         Behaviour as Kademlia,
         Config as KademliaConfig,
+        store::RecordStore,
+        GetRecordOk, InboundRequest, PeerRecord, QueryId, QueryResult, Quorum, StoreInserts,
     },
     ping::{Behaviour as Ping, Config as PingConfig},
+    kad::Mode as KademliaMode,
     mdns::{tokio::Behaviour as Mdns, Config as MdnsConfig, Event as MdnsEvent},
+    swarm::dial_opts::{DialOpts, PeerCondition},
     swarm::SwarmEvent,
+    core::transport::ListenerId,
     SwarmBuilder, PeerId,
     identify,
+    ping,
+    allow_block_list,
     relay,
+    autonat,
+    upnp,
     dcutr,
+    rendezvous,
     multiaddr::Protocol,
 };
 use tokio::sync::mpsc;
 
 // Import custom modules
-use p2p_core::identity::get_or_create_identity;
-use p2p_core::behaviour::{MyP2PBehaviour, MyP2PBehaviourEvent};
-use p2p_core::transport::build_transport;
+use p2p_core::identity::{get_or_create_identity, get_or_create_webrtc_certificate, load_pre_shared_key};
+use p2p_core::behaviour::{MyP2PBehaviour, MyP2PBehaviourEvent, Replaceable};
+use p2p_core::transport::{build_transport, DnsFallback, TransportConfig};
+use p2p_core::socks::Socks5Proxy;
+use p2p_core::limits::{LimitMetrics, Limits};
+use p2p_core::block_list::BlockList;
+use p2p_core::address_book::AddressBook;
+use p2p_core::last_seen::LastSeen;
+use p2p_core::connection_path::{ConnectionPaths, PathState};
+use p2p_core::hole_punch::{transport_name, HolePunching, MAX_HOLE_PUNCH_RETRIES};
+use p2p_core::interfaces::InterfaceWatch;
+use p2p_core::power::PowerMode;
+use p2p_core::record_store::{DhtStore, DhtStoreConfig, DHT_SAVE_INTERVAL};
+use p2p_core::peer_table::{DepartureReason, DiscoverySource, PeerEntry, PeerTable, MAX_PING_FAILURES};
+use p2p_core::presence::{accept_record, presence_record, verify_presence, Presence, PresenceStatus, PRESENCE_REPUBLISH, PRESENCE_TTL};
+use p2p_core::rendezvous::{RendezvousState, DISCOVER_LIMIT, REGISTRATION_TTL};
+use p2p_core::reconnect::{Connectivity, ReconnectSupervisor, TargetKind};
+use p2p_core::relay_discovery::{RelayCandidates, MAX_DISCOVERED_RELAYS};
+use p2p_core::network::Network;
+use p2p_core::port_mapping::PortMapping;
+use p2p_core::circuit::{circuit_addresses, is_dialable_relay_address, relay_base, reservation_address};
 pub use crate::config::AppConfig;
 use p2p_core::logger::init_p2p_logging;
 use tracing::{info, warn, error, debug};
 
+// Peers from the address book dialed on startup, most recently reached first
+const ADDRESS_BOOK_DIALS: usize = 8;
+
+// Known peers whose presence is looked up on refresh
+const PRESENCE_LOOKUPS: usize = 32;
+
+// How often connection and rejection counters are logged
+const METRICS_INTERVAL: Duration = Duration::from_secs(300);
+// Online peers' last-seen is written to disk this often, departures right away
+const LAST_SEEN_SAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+// Last-seen times, shared with the get_last_seen query
+static LAST_SEEN: std::sync::Mutex<Option<LastSeen>> = std::sync::Mutex::new(None);
+
+// Connection path of connected peers, shared with the get_connection_path query
+static CONNECTION_PATHS: std::sync::Mutex<Option<ConnectionPaths>> = std::sync::Mutex::new(None);
+
+// Hole punching statistics, shared with the get_hole_punch_stats query
+static HOLE_PUNCHING: std::sync::Mutex<Option<HolePunching>> = std::sync::Mutex::new(None);
+
 // Sends info to Flutter
 static COMMAND_SENDER: std::sync::OnceLock<mpsc::UnboundedSender<(String, String)>> = std::sync::OnceLock::new();
 
+// Ask a relay for a reservation by listening on its circuit address
+fn request_reservation(
+    swarm: &mut libp2p::Swarm<MyP2PBehaviour>,
+    relay_listeners: &mut HashMap<PeerId, ListenerId>,
+    relay_id: PeerId,
+    relay_addr: &libp2p::Multiaddr,
+) {
+    // Drop the circuit listener of a previous relay connection
+    if let Some(old_listener) = relay_listeners.remove(&relay_id) {
+        swarm.remove_listener(old_listener);
+    }
+
+    let Some(listen_addr) = reservation_address(relay_addr, relay_id) else {
+        warn!("[RELAY] No usable address for relay {}: {}", relay_id, relay_addr);
+        return;
+    };
+    info!("[RELAY] Sending reservation request via: {}", listen_addr);
+
+    match swarm.listen_on(listen_addr) {
+        Ok(listener_id) => {
+            relay_listeners.insert(relay_id, listener_id);
+        },
+        Err(e) => error!("[NETWORK] Failed to request Relay Reservation: {:?}", e),
+    }
+}
+
+// Circuit addresses to a peer through every known relay address, so the swarm can fall back between them.
+// Hierarchical address: [Physical] -> [Relay ID] -> [Circuit] -> [Peer ID]
+fn relay_circuits(
+    relays: &HashMap<PeerId, libp2p::Multiaddr>,
+    relay_listen_addrs: &HashMap<PeerId, Vec<libp2p::Multiaddr>>,
+    peer: PeerId,
+) -> Vec<libp2p::Multiaddr> {
+    let known_relay_addrs = relays.iter()
+        .map(|(id, addr)| (*id, addr))
+        .chain(relay_listen_addrs.iter()
+            .filter(|(id, _)| relays.contains_key(id))
+            .flat_map(|(id, addrs)| addrs.iter().map(move |a| (*id, a))));
+    circuit_addresses(known_relay_addrs, peer)
+}
+
+// Refuse connections from the peer (closing open ones) and drop its gossip
+fn apply_block(swarm: &mut libp2p::Swarm<MyP2PBehaviour>, peer: PeerId) {
+    let behaviour = swarm.behaviour_mut();
+    behaviour.blocked_peers.block_peer(peer);
+    behaviour.gossipsub.blacklist_peer(&peer);
+    behaviour.gossipsub.remove_explicit_peer(&peer);
+    behaviour.kademlia.remove_peer(&peer);
+    if let Some(hole_punching) = HOLE_PUNCHING.lock().unwrap().as_mut() {
+        hole_punching.forget(&peer);
+    }
+}
+
+fn apply_unblock(swarm: &mut libp2p::Swarm<MyP2PBehaviour>, peer: PeerId) {
+    let behaviour = swarm.behaviour_mut();
+    behaviour.blocked_peers.unblock_peer(peer);
+    behaviour.gossipsub.remove_blacklisted_peer(&peer);
+}
+
+// Listen on all interfaces. QUIC and WebRTC bypass the pre-shared key, private networks stay on TCP
+fn listen_direct(swarm: &mut libp2p::Swarm<MyP2PBehaviour>, tcp: bool, udp: bool) -> Vec<ListenerId> {
+    let mut addrs = Vec::new();
+    if tcp {
+        addrs.push("/ip4/0.0.0.0/tcp/0");
+    }
+    if udp {
+        addrs.push("/ip4/0.0.0.0/udp/0/quic-v1");
+        // Browser peers (web build) can only dial WebRTC-direct
+        addrs.push("/ip4/0.0.0.0/udp/0/webrtc-direct");
+    }
+    let mut listeners = Vec::new();
+    for addr in addrs {
+        match swarm.listen_on(addr.parse::<libp2p::Multiaddr>().unwrap()) {
+            Ok(id) => listeners.push(id),
+            Err(e) => warn!("Failed to listen on {}: {}", addr, e),
+        }
+    }
+    listeners
+}
+
+// Gossipsub with the heartbeat of the power mode
+fn gossipsub_behaviour(keypair: &libp2p::identity::Keypair, network: &Network, mode: PowerMode) -> gossipsub::Behaviour {
+    gossipsub::Behaviour::new(
+        gossipsub::MessageAuthenticity::Signed(keypair.clone()),
+        network.gossipsub_config()
+            .heartbeat_interval(mode.heartbeat_interval())
+            .validation_mode(gossipsub::ValidationMode::Strict)
+            .build().unwrap(),
+    ).expect("Gossipsub error")
+}
+
+// Relay circuit is only a fallback when we are not reachable directly
+fn relay_needed(nat_status: &autonat::NatStatus, port_mapping: &PortMapping) -> bool {
+    match nat_status {
+        autonat::NatStatus::Public(_) => false,
+        autonat::NatStatus::Private => true,
+        autonat::NatStatus::Unknown => port_mapping.needs_relay(),
+    }
+}
+
+// Hold reservations on every connected relay, or release them all
+fn update_reservations(
+    swarm: &mut libp2p::Swarm<MyP2PBehaviour>,
+    relays: &HashMap<PeerId, libp2p::Multiaddr>,
+    relay_listeners: &mut HashMap<PeerId, ListenerId>,
+    needed: bool,
+) {
+    if !needed {
+        for (relay_id, listener_id) in relay_listeners.drain() {
+            info!("[RELAY] Reachable directly, dropping reservation on {}", relay_id);
+            swarm.remove_listener(listener_id);
+        }
+        return;
+    }
+
+    let missing: Vec<(PeerId, libp2p::Multiaddr)> = relays.iter()
+        .filter(|(id, _)| swarm.is_connected(id) && !relay_listeners.contains_key(id))
+        .map(|(id, addr)| (*id, addr.clone()))
+        .collect();
+    for (relay_id, relay_addr) in missing {
+        request_reservation(swarm, relay_listeners, relay_id, &relay_addr);
+    }
+}
+
+// Register our namespaces at every connected relay and fetch registrations we have not seen yet
+fn sync_rendezvous(
+    swarm: &mut libp2p::Swarm<MyP2PBehaviour>,
+    relays: &HashMap<PeerId, libp2p::Multiaddr>,
+    state: &mut RendezvousState,
+) {
+    let points: Vec<PeerId> = relays.keys().filter(|id| swarm.is_connected(id)).copied().collect();
+    // Registering needs an external address, a relay reservation provides one
+    let registrable = swarm.external_addresses().next().is_some();
+    for point in points {
+        if registrable {
+            for namespace in state.due_registrations(point, Instant::now()) {
+                if let Err(e) = swarm.behaviour_mut().rendezvous.register(namespace.clone(), point, Some(REGISTRATION_TTL)) {
+                    warn!("[RENDEZVOUS] Cannot register '{}' at {}: {:?}", namespace, point, e);
+                    state.on_register_failed(point, namespace);
+                }
+            }
+        }
+        for namespace in state.namespaces() {
+            let cookie = state.cookie(point, &namespace);
+            swarm.behaviour_mut().rendezvous.discover(Some(namespace), cookie, Some(DISCOVER_LIMIT), point);
+        }
+    }
+}
+
+// Sign and store our presence record, replacing the old gossip announcements.
+// While invisible the record says so, replacing the visible one other nodes still hold
+fn publish_presence(
+    swarm: &mut libp2p::Swarm<MyP2PBehaviour>,
+    keypair: &libp2p::identity::Keypair,
+    network: &Network,
+    presence: &Presence,
+    share_last_seen: bool,
+) {
+    let record = presence_record(keypair, network, presence, share_last_seen);
+    if let Err(e) = swarm.behaviour_mut().kademlia.put_record(record, Quorum::One) {
+        warn!("[PRESENCE] Cannot store presence record: {:?}", e);
+    }
+}
+
+// Fetch a peer's presence record, unless a lookup is already running
+fn lookup_presence(
+    swarm: &mut libp2p::Swarm<MyP2PBehaviour>,
+    network: &Network,
+    lookups: &mut HashMap<QueryId, (PeerId, bool)>,
+    peer: PeerId,
+) {
+    if lookups.values().any(|(p, _)| *p == peer) {
+        return;
+    }
+    let query_id = swarm.behaviour_mut().kademlia.get_record(network.presence_key(&peer));
+    lookups.insert(query_id, (peer, false));
+}
+
+// STATUS:<peer>:<ONLINE|AWAY|DND|INVISIBLE>:<status line>
+fn status_event(peer: &PeerId, presence: &Presence) -> String {
+    format!("STATUS:{}:{}", peer, presence)
+}
+
+// PEER+:<peer>:<source it was first seen through>
+fn peer_joined_event(peer: &PeerId, source: DiscoverySource) -> String {
+    format!("PEER+:{}:{}", peer, source.as_str())
+}
+
+// PEER-:<peer>:<last seen, unix seconds, empty if the peer does not share it>:<reason>
+fn peer_left_event(peer: &PeerId, last_seen: Option<u64>, reason: DepartureReason) -> String {
+    let secs = last_seen.map(|secs| secs.to_string()).unwrap_or_default();
+    format!("PEER-:{}:{}:{}", peer, secs, reason.as_str())
+}
+
+// Records when a departing peer was last seen, None if it does not share that
+fn record_departure(peer: &PeerId, entry: &PeerEntry) -> Option<u64> {
+    if !entry.shows_last_seen() {
+        return None;
+    }
+    let mut last_seen = LAST_SEEN.lock().unwrap();
+    let last_seen = last_seen.as_mut()?;
+    let secs = last_seen.touch(*peer);
+    last_seen.save_if_dirty();
+    Some(secs)
+}
+
+// PATH:<peer>:<RELAYED|HOLE_PUNCHING|DIRECT|FAILED, empty once disconnected>
+fn path_event(peer: &PeerId, state: Option<PathState>) -> String {
+    format!("PATH:{}:{}", peer, state.map(|s| s.as_str()).unwrap_or_default())
+}
+
+// PING:<peer>:<rtt ms, empty until one succeeded>:<failures in a row>:<failures in total>
+fn ping_event(peer: &PeerId, entry: &PeerEntry) -> String {
+    let rtt = entry.rtt.map(|rtt| rtt.as_millis().to_string()).unwrap_or_default();
+    format!("PING:{}:{}:{}:{}", peer, rtt, entry.consecutive_failures, entry.ping_failures)
+}
+
+fn nat_status_str(status: &autonat::NatStatus) -> &'static str {
+    match status {
+        autonat::NatStatus::Public(_) => "PUBLIC",
+        autonat::NatStatus::Private => "PRIVATE",
+        autonat::NatStatus::Unknown => "UNKNOWN",
+    }
+}
+
+// Notify Flutter only when the overall connectivity changes
+fn report_connectivity(
+    sink: &StreamSink<String>,
+    supervisor: &ReconnectSupervisor,
+    last: &mut Option<Connectivity>,
+) {
+    let current = supervisor.connectivity();
+    if *last != Some(current) {
+        info!("[NETWORK] Connectivity: {}", current.as_str());
+        let _ = sink.add(format!("CONNECTIVITY:{}", current.as_str()));
+        *last = Some(current);
+    }
+}
+
 #[frb(sync)]
 pub fn send_message(recipient: String, msg: String) {
     if let Some(sender) = COMMAND_SENDER.get() {
@@ -54,33 +340,121 @@ pub async fn start_p2p_node(
     let _log_guard = init_p2p_logging(&storage_path, &peer_id);
     info!("Instance '{}' initialized correctly", instance_name);
 
+    // Private network key. A broken key must not silently fall back to the public network
+    let pre_shared_key = match load_pre_shared_key(&storage_path, &config.pre_shared_key) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("[PNET] {}", e);
+            let _ = sink.add("PNET:INVALID".to_string());
+            return;
+        },
+    };
+    let _ = sink.add(format!("PNET:{}", if pre_shared_key.is_some() { "PRIVATE" } else { "PUBLIC" }));
+
+    // Outbound SOCKS5 proxy (e.g. Tor). Proxy-only without a usable proxy would leak, so refuse to start
+    let socks5_proxy = match config.socks5_proxy.trim() {
+        "" => None,
+        value => match Socks5Proxy::parse(value) {
+            Ok(proxy) => Some(proxy),
+            Err(e) => {
+                error!("[PROXY] {}", e);
+                let _ = sink.add("PROXY:INVALID".to_string());
+                return;
+            },
+        },
+    };
+    if config.proxy_only && socks5_proxy.is_none() {
+        error!("[PROXY] Proxy-only mode needs a SOCKS5 proxy");
+        let _ = sink.add("PROXY:INVALID".to_string());
+        return;
+    }
+    let _ = sink.add(format!("PROXY:{}", match (&socks5_proxy, config.proxy_only) {
+        (None, _) => "OFF",
+        (Some(_), false) => "ON",
+        (Some(_), true) => "ONLY",
+    }));
+
+    // Resolvers for where the OS DNS configuration is unreadable
+    let dns_fallback = DnsFallback::parse(&config.dns_fallback).unwrap_or_else(|e| {
+        warn!("[DNS] {}, using {}", e, DnsFallback::default());
+        DnsFallback::default()
+    });
+
     // Transport
-    let (transport, relay_client) = build_transport(&id_keys, peer_id);
+    let transport_config = TransportConfig::default()
+        .with_webrtc(get_or_create_webrtc_certificate(&storage_path, &instance_name))
+        .with_pre_shared_key(pre_shared_key)
+        .with_socks5_proxy(socks5_proxy, config.proxy_only)
+        .with_dns_fallback(dns_fallback);
+    // Proxy-only: nothing that reveals or probes our own address
+    let direct = transport_config.direct_enabled();
+    if !direct {
+        info!("[PROXY] Proxy-only mode: mDNS, listening, DCUtR and UPnP disabled");
+    }
+    let enable_upnp = config.enable_upnp && direct;
+    let (transport, relay_client) = build_transport(&id_keys, peer_id, &transport_config);
+
+    // Topic and protocol names of our network
+    let network = match Network::new(&config.network_id) {
+        Ok(network) => network,
+        Err(e) => {
+            error!("[NETWORK] {}", e);
+            let _ = sink.add("NETWORK:INVALID".to_string());
+            return;
+        },
+    };
+    info!("Network: '{}' (topic {})", network.id(), network.topic_name());
 
     // Behaviours
     let mut kad_config = KademliaConfig::default();
-    kad_config.set_protocol_names(vec![libp2p::StreamProtocol::new("/p2p_msg/kad/1.0.0")]);
+    kad_config.set_protocol_names(vec![network.kad_protocol()]);
+    // Inbound records are checked before they are stored, forged presence records are dropped
+    kad_config.set_record_filtering(StoreInserts::FilterBoth);
+
+    // Connection caps, AppConfig overrides the defaults
+    let mut limits = Limits::node();
+    if config.max_connections > 0 {
+        limits.max_established = Some(config.max_connections.into());
+    }
+    if config.max_connections_per_peer > 0 {
+        limits.max_established_per_peer = Some(config.max_connections_per_peer.into());
+    }
+    info!("[LIMITS] {}", limits);
+
+    // DHT records in memory, or in <storage_path>/dht_<instance>.redb when persist_dht is set
+    let dht_store = if config.persist_dht {
+        let path = std::path::Path::new(&storage_path).join(format!("dht_{}.redb", instance_name));
+        DhtStore::persistent(peer_id, path, DhtStoreConfig::default())
+    } else {
+        DhtStore::in_memory(peer_id, DhtStoreConfig::default())
+    };
 
     let behaviour = MyP2PBehaviour {
-        kademlia: Kademlia::with_config(peer_id, MemoryStore::new(peer_id), kad_config),
-        ping: Ping::new(PingConfig::new().with_interval(Duration::from_secs(30))),
-        mdns: Mdns::new(MdnsConfig::default(), peer_id).expect("mDNS error"),
-        gossipsub: gossipsub::Behaviour::new(
-            gossipsub::MessageAuthenticity::Signed(id_keys.clone()),
-            gossipsub::ConfigBuilder::default()
-                .heartbeat_interval(Duration::from_secs(1))
-                .validation_mode(gossipsub::ValidationMode::Strict)
-                .build().unwrap(),
-        ).expect("Gossipsub error"),
+        blocked_peers: allow_block_list::Behaviour::default(),
+        connection_limits: limits.connection_limits(),
+        memory_limits: limits.memory_limits(),
+        kademlia: Kademlia::with_config(peer_id, dht_store, kad_config),
+        ping: Ping::new(PingConfig::new().with_interval(PowerMode::default().ping_interval())),
+        mdns: direct.then(|| Mdns::new(MdnsConfig::default(), peer_id).expect("mDNS error")).into(),
+        gossipsub: Replaceable::new(gossipsub_behaviour(&id_keys, &network, PowerMode::default())),
         identify: identify::Behaviour::new(identify::Config::new(
-            "/p2p_msg/id/1.0.0".to_string(),
+            network.identify_protocol(),
             id_keys.public()
         )),
         relay_client,
         relay_server: relay::Behaviour::new(peer_id, relay::Config::default()),
-        dcutr_handler: dcutr::Behaviour::new(peer_id),
+        dcutr_handler: direct.then(|| dcutr::Behaviour::new(peer_id)).into(),
+        autonat: autonat::Behaviour::new(peer_id, autonat::Config {
+            boot_delay: Duration::from_secs(5),
+            ..Default::default()
+        }),
+        upnp: enable_upnp.then(upnp::tokio::Behaviour::default).into(),
+        rendezvous: rendezvous::client::Behaviour::new(id_keys.clone()),
     };
 
+    // Signs our presence records
+    let presence_keys = id_keys.clone();
+
     // Swarm
     let mut swarm = SwarmBuilder::with_existing_identity(id_keys)
         .with_tokio()
@@ -89,39 +463,61 @@ pub async fn start_p2p_node(
         .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
         .build();
 
-    let topic = gossipsub::IdentTopic::new("p2p-chat-global");
+    let topic = network.topic();
     swarm.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
 
+    // Blocked peers are refused at the connection level and ignored by gossipsub
+    let mut block_list = BlockList::load(&storage_path, &instance_name);
+    for peer in block_list.peers() {
+        apply_block(&mut swarm, *peer);
+        let _ = sink.add(format!("BLOCKED:{}", peer));
+    }
+
     // Config listening
-    let listen_addr = "/ip4/0.0.0.0/tcp/0".parse::<libp2p::Multiaddr>().unwrap();
-    swarm.listen_on(listen_addr).expect("Failed to listen on TCP");
-    let quic_listen_addr = "/ip4/0.0.0.0/udp/0/quic-v1".parse::<libp2p::Multiaddr>().unwrap();
-    swarm.listen_on(quic_listen_addr).expect("Failed to listen on QUIC");
-    let mut relay_peer_id: Option<PeerId> = None;
-    let mut relay_address_to_dial: Option<libp2p::Multiaddr> = None;
+    let udp = transport_config.udp_transports_enabled();
+    let mut direct_listeners = listen_direct(&mut swarm, direct, udp);
+    // Desktops notice network changes themselves, mobile apps report them through notify_network_changed
+    let mut interface_watch = InterfaceWatch::new();
+    let watch_interfaces = !cfg!(any(target_os = "android", target_os = "ios"));
+    // Active relays: peer -> physical address (ending in /p2p/<relay id>)
+    let mut relays: HashMap<PeerId, libp2p::Multiaddr> = HashMap::new();
+    let mut relay_listeners: HashMap<PeerId, ListenerId> = HashMap::new();
+    // Further addresses the relays reported via identify (QUIC, IPv6, WebSocket...)
+    let mut relay_listen_addrs: HashMap<PeerId, Vec<libp2p::Multiaddr>> = HashMap::new();
+    let mut relay_candidates = RelayCandidates::new();
+    let discover_relays = config.relay_address.is_empty();
+    let mut nat_status = autonat::NatStatus::Unknown;
+    let mut port_mapping = PortMapping::new(enable_upnp);
+    let mut supervisor = ReconnectSupervisor::new();
+    let mut last_connectivity: Option<Connectivity> = None;
+    // Rendezvous registrations at the relays: the whole network plus the rooms we joined
+    let mut rendezvous_state = RendezvousState::new(
+        network.rendezvous_namespace(None).expect("Checked by Network::new"),
+    );
 
     // Connect to relay
     if !config.relay_address.is_empty() {
         if let Ok(relay_addr) = config.relay_address.parse::<libp2p::Multiaddr>() {
             info!("Trying to listen via Relay: {:?}", relay_addr);
-            relay_address_to_dial = Some(relay_addr.clone());
 
-            relay_peer_id = relay_addr.iter().find_map(|p| match p {
+            let relay_peer_id = relay_addr.iter().find_map(|p| match p {
                 Protocol::P2p(id) => Some(id),
                 _ => None,
             });
 
-            let mut physical_addr = relay_addr.clone();
-            if let Some(Protocol::P2pCircuit) = physical_addr.pop() {
-                info!("Dialing physical Relay address: {:?}", physical_addr);
-                if let Err(e) = swarm.dial(physical_addr) {
-                    warn!("Error dialing Relay: {:?}", e);
-                }
-            } else {
-                info!("Address did not end in p2p-circuit, dialing original...");
-                if let Err(e) = swarm.dial(relay_addr.clone()) {
-                    warn!("Error dialing Relay: {:?}", e);
-                }
+            // Physical address: full transport stack up to /p2p/<relay id>, without /p2p-circuit
+            match relay_peer_id.and_then(|id| Some((id, relay_base(&relay_addr, id)?))) {
+                Some((id, physical_addr)) => {
+                    info!("Dialing physical Relay address: {:?}", physical_addr);
+                    relays.insert(id, physical_addr.clone());
+                    supervisor.watch(id, physical_addr.clone(), TargetKind::Relay);
+                    swarm.behaviour_mut().autonat.add_server(id, Some(physical_addr.clone()));
+                    if let Err(e) = swarm.dial(physical_addr) {
+                        warn!("Error dialing Relay: {:?}", e);
+                        supervisor.on_disconnected(&id);
+                    }
+                },
+                None => warn!("Relay address {} does not contain a usable /p2p/<relay id>", relay_addr),
             }
         }
     }
@@ -131,11 +527,13 @@ pub async fn start_p2p_node(
         if let Ok(mut addr) = addr_str.parse::<libp2p::Multiaddr>() {
             if let Some(Protocol::P2p(remote_peer_id)) = addr.pop() {
                 swarm.behaviour_mut().kademlia.add_address(&remote_peer_id, addr.clone());
-                if Some(remote_peer_id) != relay_peer_id {
+                if !relays.contains_key(&remote_peer_id) {
                     info!("Dialing bootstrap node: {:?}", addr);
                     let full_addr = addr.with(Protocol::P2p(remote_peer_id));
+                    supervisor.watch(remote_peer_id, full_addr.clone(), TargetKind::Bootstrap);
                     if let Err(e) = swarm.dial(full_addr) {
                         error!("Error dialing bootstrap: {:?}", e);
+                        supervisor.on_disconnected(&remote_peer_id);
                     }
                 } else {
                     info!("Bootstrap node is the Relay. Skipping double dial.");
@@ -144,11 +542,64 @@ pub async fn start_p2p_node(
         }
     }
 
+    // Peers known from previous runs: seed the routing table and redial the last reachable ones,
+    // so startup does not depend on the relay and bootstrap nodes alone
+    let mut address_book = AddressBook::load(&storage_path, &instance_name);
+    if !address_book.is_empty() {
+        info!("[ADDRESS BOOK] Seeding Kademlia with {} known peers", address_book.len());
+        for (peer, addrs) in address_book.peers() {
+            if peer == *swarm.local_peer_id() || block_list.contains(&peer) {
+                continue;
+            }
+            for addr in addrs {
+                swarm.behaviour_mut().kademlia.add_address(&peer, addr);
+            }
+        }
+        let _ = swarm.behaviour_mut().kademlia.bootstrap();
+
+        let candidates = address_book.dial_candidates(ADDRESS_BOOK_DIALS + relays.len());
+        for (peer, addrs) in candidates.into_iter()
+            .filter(|(peer, _)| !relays.contains_key(peer) && !block_list.contains(peer))
+            .take(ADDRESS_BOOK_DIALS)
+        {
+            info!("[ADDRESS BOOK] Dialing known peer {} ({} addresses)", peer, addrs.len());
+            if let Err(e) = swarm.dial(DialOpts::peer_id(peer).addresses(addrs).build()) {
+                debug!("[ADDRESS BOOK] Could not dial {}: {:?}", peer, e);
+            }
+        }
+    }
+
     let (tx, mut rx) = mpsc::unbounded_channel::<(String, String)>();
-    let _ = COMMAND_SENDER.set(tx.clone());
-    let tx_inner = tx.clone();
-    let mut peers_last_seen: HashMap<PeerId, Instant> = HashMap::new();
-    let mut discovery_interval = tokio::time::interval(Duration::from_secs(15));
+    let _ = COMMAND_SENDER.set(tx);
+    // Peers shown as online, with presence expiry and ping statistics
+    let mut peer_table = PeerTable::new();
+    // Running presence lookups -> (peer, valid record found)
+    let mut presence_lookups: HashMap<QueryId, (PeerId, bool)> = HashMap::new();
+    let mut presence_interval = tokio::time::interval(PRESENCE_REPUBLISH);
+    // Our status, set from Flutter
+    let mut own_presence = Presence::default();
+    let share_last_seen = !config.hide_last_seen;
+    *LAST_SEEN.lock().unwrap() = Some(LastSeen::load(&storage_path, &instance_name));
+    *CONNECTION_PATHS.lock().unwrap() = Some(ConnectionPaths::new());
+    *HOLE_PUNCHING.lock().unwrap() = Some(HolePunching::new());
+    // Peers of other networks seen on the LAN, ignored from then on
+    let mut foreign_peers: HashSet<PeerId> = HashSet::new();
+    // Set from the app lifecycle, Background and Suspended save battery
+    let mut power_mode = PowerMode::default();
+    let mut discovery_interval = tokio::time::interval(power_mode.discovery_interval());
+    let mut reconnect_interval = tokio::time::interval(Duration::from_secs(1));
+    let mut metrics_interval = tokio::time::interval(METRICS_INTERVAL);
+    let mut last_seen_interval = tokio::time::interval(LAST_SEEN_SAVE_INTERVAL);
+    let mut dht_save_interval = tokio::time::interval(DHT_SAVE_INTERVAL);
+    let mut limit_metrics = LimitMetrics::default();
+    report_connectivity(&sink, &supervisor, &mut last_connectivity);
+    let _ = sink.add(format!("NAT:{}", nat_status_str(&nat_status)));
+    let _ = sink.add(format!("UPNP:{}", port_mapping.as_str()));
+
+    if discover_relays {
+        info!("[RELAY] No relay configured, searching the DHT for relay providers...");
+        swarm.behaviour_mut().kademlia.get_providers(network.relay_provider_key());
+    }
 
     // Event loop
     loop {
@@ -157,7 +608,10 @@ pub async fn start_p2p_node(
             Some((recipient, msg_to_send)) = rx.recv() => {
                 if recipient == "REFRESH" {
                     info!("Refreshing node discovery...");
-                    peers_last_seen.clear();
+                    peer_table.clear();
+
+                    // Skip pending backoffs for relay and bootstrap peers
+                    supervisor.retry_all_now();
 
                     // Refresh network
                     let _ = swarm.behaviour_mut().kademlia.bootstrap();
@@ -166,23 +620,166 @@ pub async fn start_p2p_node(
                     let random_peer = PeerId::random();
                     swarm.behaviour_mut().kademlia.get_closest_peers(random_peer);
 
-                    // Announce presence via GossipSub
-                    let topic = gossipsub::IdentTopic::new("p2p-chat-global");
-                    let msg = "ANNOUNCE:REFRESH";
-                    if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic, msg.as_bytes()) {
-                        error!("Error publishing refresh: {:?}", e);
+                    // Announce ourselves and ask the DHT which known peers are around
+                    publish_presence(&mut swarm, &presence_keys, &network, &own_presence, share_last_seen);
+                    let known: Vec<PeerId> = address_book.peers().into_iter()
+                        .map(|(peer, _)| peer)
+                        .filter(|peer| !swarm.is_connected(peer) && !block_list.contains(peer) && !relays.contains_key(peer))
+                        .take(PRESENCE_LOOKUPS)
+                        .collect();
+                    for peer in known {
+                        lookup_presence(&mut swarm, &network, &mut presence_lookups, peer);
                     }
 
                     // Send known peers to Flutter
                     for peer_id in swarm.connected_peers() {
-                        if Some(*peer_id) != relay_peer_id {
-                            if peers_last_seen.insert(*peer_id, Instant::now()).is_none() {
-                                let _ = sink.add(format!("PEER+:{}", peer_id));
-                            }
+                        if !relays.contains_key(peer_id) && !relay_candidates.contains(peer_id) && peer_table.mark_online(*peer_id, DiscoverySource::Connection, Instant::now() + PRESENCE_TTL) {
+                            let _ = sink.add(peer_joined_event(peer_id, DiscoverySource::Connection));
+                        }
+                    }
+                } else if recipient == "BLOCK" || recipient == "UNBLOCK" {
+                    let Ok(peer) = msg_to_send.parse::<PeerId>() else {
+                        warn!("[BLOCK] Invalid peer id: {}", msg_to_send);
+                        continue;
+                    };
+                    if recipient == "BLOCK" {
+                        if block_list.block(peer) {
+                            info!("[BLOCK] Blocked peer {}", peer);
+                        }
+                        address_book.remove(&peer);
+                        apply_block(&mut swarm, peer);
+                        if let Some(entry) = peer_table.remove(&peer, DepartureReason::Blocked) {
+                            let seen = record_departure(&peer, &entry);
+                            let _ = sink.add(peer_left_event(&peer, seen, DepartureReason::Blocked));
+                        }
+                        let _ = sink.add(format!("BLOCKED:{}", peer));
+                    } else {
+                        if block_list.unblock(&peer) {
+                            info!("[BLOCK] Unblocked peer {}", peer);
+                        }
+                        apply_unblock(&mut swarm, peer);
+                        let _ = sink.add(format!("UNBLOCKED:{}", peer));
+                    }
+                } else if recipient == "PRESENCE" {
+                    match msg_to_send.parse::<PeerId>() {
+                        Ok(peer) => lookup_presence(&mut swarm, &network, &mut presence_lookups, peer),
+                        Err(_) => warn!("[PRESENCE] Invalid peer id: {}", msg_to_send),
+                    }
+                } else if recipient == "STATUS" {
+                    let Some(presence) = Presence::parse(&msg_to_send) else {
+                        warn!("[PRESENCE] Invalid status: {}", msg_to_send);
+                        continue;
+                    };
+                    if presence != own_presence {
+                        info!("[PRESENCE] Status changed to {}", presence);
+                        own_presence = presence;
+                        publish_presence(&mut swarm, &presence_keys, &network, &own_presence, share_last_seen);
+                        // Connected peers learn the change right away, everyone else from the record.
+                        // Going invisible is announced too, so they stop showing us online
+                        let msg = format!("ANNOUNCE:STATUS:{}", own_presence);
+                        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(network.topic(), msg.as_bytes()) {
+                            debug!("[PRESENCE] Status announcement not sent: {:?}", e);
+                        }
+                    }
+                } else if recipient == "JOIN_ROOM" {
+                    let Some(namespace) = network.rendezvous_namespace(Some(&msg_to_send)) else {
+                        warn!("[RENDEZVOUS] Room name too long: {}", msg_to_send);
+                        continue;
+                    };
+                    if rendezvous_state.join_room(namespace, msg_to_send.clone()) {
+                        info!("[RENDEZVOUS] Joined room '{}'", msg_to_send);
+                        sync_rendezvous(&mut swarm, &relays, &mut rendezvous_state);
+                    }
+                } else if recipient == "POWER" {
+                    let Some(mode) = PowerMode::parse(&msg_to_send) else {
+                        warn!("[POWER] Invalid power mode: {}", msg_to_send);
+                        continue;
+                    };
+                    if mode == power_mode {
+                        continue;
+                    }
+                    info!("[POWER] {} -> {}", power_mode.as_str(), mode.as_str());
+                    let resumed = mode.keeps_peers() && !power_mode.keeps_peers();
+                    power_mode = mode;
+                    // The first tick comes right away, which also catches up on resume
+                    discovery_interval = tokio::time::interval(mode.discovery_interval());
+                    // Ping interval and gossipsub heartbeat are fixed when built, so both are replaced
+                    swarm.behaviour_mut().ping = Ping::new(PingConfig::new().with_interval(mode.ping_interval()));
+                    swarm.behaviour_mut().gossipsub.replace(gossipsub_behaviour(&presence_keys, &network, mode));
+                    if let Err(e) = swarm.behaviour_mut().gossipsub.subscribe(&topic) {
+                        error!("[POWER] Cannot subscribe to {}: {:?}", topic, e);
+                    }
+                    for peer in block_list.peers() {
+                        swarm.behaviour_mut().gossipsub.blacklist_peer(peer);
+                    }
+
+                    let mdns_enabled = swarm.behaviour().mdns.is_enabled();
+                    if mode.active_discovery() && direct && !mdns_enabled {
+                        match Mdns::new(MdnsConfig::default(), peer_id) {
+                            Ok(mdns) => swarm.behaviour_mut().mdns = Some(mdns).into(),
+                            Err(e) => warn!("[POWER] Cannot restart mDNS: {}", e),
+                        }
+                    } else if !mode.active_discovery() && mdns_enabled {
+                        swarm.behaviour_mut().mdns = None.into();
+                    }
+
+                    // Open connections still run the old handlers: close them, the relays are redialed by
+                    // the supervisor and other peers right here. Relay candidates only live until selection
+                    let connected: Vec<PeerId> = swarm.connected_peers()
+                        .filter(|peer| !relay_candidates.contains(peer))
+                        .copied()
+                        .collect();
+                    for peer in connected {
+                        let _ = swarm.disconnect_peer_id(peer);
+                        if relays.contains_key(&peer) || !mode.keeps_peers() {
+                            continue;
+                        }
+                        let opts = DialOpts::peer_id(peer)
+                            .condition(PeerCondition::Always)
+                            .addresses(relay_circuits(&relays, &relay_listen_addrs, peer))
+                            .extend_addresses_through_behaviour()
+                            .build();
+                        if let Err(e) = swarm.dial(opts) {
+                            debug!("[POWER] Could not reopen connection to {}: {:?}", peer, e);
+                        }
+                    }
+
+                    if resumed {
+                        // Our presence record ran out while suspended
+                        supervisor.retry_all_now();
+                        let _ = swarm.behaviour_mut().kademlia.bootstrap();
+                        publish_presence(&mut swarm, &presence_keys, &network, &own_presence, share_last_seen);
+                    }
+                } else if recipient == "NETWORK_CHANGED" {
+                    if !interface_watch.should_handle(Instant::now()) {
+                        debug!("[NETWORK] Network changed again, handling it once the last change settled");
+                        continue;
+                    }
+                    info!("[NETWORK] Network changed, re-listening and reconnecting to the relays");
+                    for listener in direct_listeners.drain(..) {
+                        swarm.remove_listener(listener);
+                    }
+                    direct_listeners = listen_direct(&mut swarm, direct, udp);
+
+                    // Connections over the old network are dead. The supervisor redials the relays once
+                    // they are closed, and the fresh connection renews the reservation and the rendezvous registrations
+                    for relay_id in relays.keys() {
+                        rendezvous_state.forget_point(relay_id);
+                        let _ = swarm.disconnect_peer_id(*relay_id);
+                    }
+                    supervisor.retry_all_now();
+                    let _ = swarm.behaviour_mut().kademlia.bootstrap();
+                    publish_presence(&mut swarm, &presence_keys, &network, &own_presence, share_last_seen);
+                } else if recipient == "LEAVE_ROOM" {
+                    if let Some(namespace) = rendezvous_state.leave_room(&msg_to_send) {
+                        info!("[RENDEZVOUS] Left room '{}'", msg_to_send);
+                        let points: Vec<PeerId> = relays.keys().filter(|id| swarm.is_connected(id)).copied().collect();
+                        for point in points {
+                            swarm.behaviour_mut().rendezvous.unregister(namespace.clone(), point);
                         }
                     }
                 } else {
-                    let topic = gossipsub::IdentTopic::new("p2p-chat-global");
+                    let topic = network.topic();
                     // Publish message
                     if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic, msg_to_send.as_bytes()) {
                         error!("Publish error: {e:?}");
@@ -195,32 +792,134 @@ pub async fn start_p2p_node(
                 }
             }
 
+            // Redial relay and bootstrap peers whose backoff expired
+            _ = reconnect_interval.tick() => {
+                for (peer, addr, attempt) in supervisor.due(Instant::now()) {
+                    info!("[RECONNECT] Redialing {} (attempt {}): {}", peer, attempt, addr);
+                    if let Err(e) = swarm.dial(addr) {
+                        warn!("[RECONNECT] Error redialing {}: {:?}", peer, e);
+                        supervisor.on_disconnected(&peer);
+                    }
+                }
+
+                // Reserve on the best discovered relays once their RTT is known
+                if discover_relays && relays.len() < MAX_DISCOVERED_RELAYS {
+                    let exclude: Vec<PeerId> = relays.keys().copied().collect();
+                    for (relay_id, relay_addr) in relay_candidates.select(MAX_DISCOVERED_RELAYS - relays.len(), &exclude) {
+                        info!("[RELAY] Selected discovered relay {} at {}", relay_id, relay_addr);
+                        relays.insert(relay_id, relay_addr.clone());
+                        supervisor.watch(relay_id, relay_addr.clone(), TargetKind::Relay);
+                        supervisor.on_connected(&relay_id);
+                        swarm.behaviour_mut().autonat.add_server(relay_id, Some(relay_addr.clone()));
+                        if relay_needed(&nat_status, &port_mapping) {
+                            request_reservation(&mut swarm, &mut relay_listeners, relay_id, &relay_addr);
+                        }
+                    }
+                }
+
+                // Network change that came in right after another one
+                if interface_watch.deferred_due(Instant::now()) {
+                    if let Some(sender) = COMMAND_SENDER.get() {
+                        let _ = sender.send(("NETWORK_CHANGED".to_string(), String::new()));
+                    }
+                }
+
+                report_connectivity(&sink, &supervisor, &mut last_connectivity);
+            }
+
+            // Suspended nodes let their presence expire
+            _ = presence_interval.tick(), if power_mode.keeps_peers() => {
+                publish_presence(&mut swarm, &presence_keys, &network, &own_presence, share_last_seen);
+            }
+
+            _ = metrics_interval.tick() => {
+                info!("[METRICS] {} connected peers, {}", swarm.network_info().num_peers(), limit_metrics);
+                if let Some(hole_punching) = HOLE_PUNCHING.lock().unwrap().as_ref() {
+                    info!("[METRICS] {}", hole_punching.summary());
+                }
+            }
+
+            // DHT records stored since the last tick, in one write
+            _ = dht_save_interval.tick() => {
+                swarm.behaviour_mut().kademlia.store_mut().save_if_dirty();
+            }
+
+            // Online peers are seen now, in case the app is killed before they leave
+            _ = last_seen_interval.tick() => {
+                if let Some(last_seen) = LAST_SEEN.lock().unwrap().as_mut() {
+                    for peer in peer_table.sharing_last_seen() {
+                        last_seen.touch(peer);
+                    }
+                    last_seen.save_if_dirty();
+                }
+            }
+
             _ = discovery_interval.tick() => {
-                if let Some(_) = relay_peer_id {
+                if watch_interfaces && interface_watch.poll() {
+                    if let Some(sender) = COMMAND_SENDER.get() {
+                        let _ = sender.send(("NETWORK_CHANGED".to_string(), String::new()));
+                    }
+                }
+                address_book.save_if_dirty();
+                sync_rendezvous(&mut swarm, &relays, &mut rendezvous_state);
+
+                // Keep looking for relays until enough were found
+                if power_mode.active_discovery() && discover_relays && relays.len() < MAX_DISCOVERED_RELAYS {
+                    swarm.behaviour_mut().kademlia.get_providers(network.relay_provider_key());
+                }
+
+                if power_mode.active_discovery() && !relays.is_empty() {
                     // Refresh Kademlia
                     let random_peer = PeerId::random();
                     swarm.behaviour_mut().kademlia.get_closest_peers(random_peer);
+                }
 
-                    // Keep Gossipsub alive (heartbeat)
-                    let topic = gossipsub::IdentTopic::new("p2p-chat-global");
-                    let msg = "ANNOUNCE:REFRESH";
+                // Relayed peers whose hole punch never reported back
+                let stalled = CONNECTION_PATHS.lock().unwrap().as_mut()
+                    .map(|paths| paths.expire_hole_punching(Instant::now()))
+                    .unwrap_or_default();
+                for peer in stalled {
+                    info!("[DCUTR] No hole punching result from {}, staying on the relay", peer);
+                    if let Some(hole_punching) = HOLE_PUNCHING.lock().unwrap().as_mut() {
+                        hole_punching.abandon(&peer);
+                    }
+                    let _ = sink.add(path_event(&peer, Some(PathState::Relayed)));
+                }
 
-                    let _ = swarm.behaviour_mut().gossipsub.publish(topic, msg.as_bytes());
+                // Suspended: no lookups, they would dial other peers
+                if !power_mode.keeps_peers() {
+                    continue;
+                }
 
-                    // Clean Peers
-                    let now = Instant::now();
-                    let timeout = Duration::from_secs(45);
+                // Connected peers stay online. For the others the presence record decides:
+                // once it runs out it is looked up again, and the peer goes offline if none is found
+                let now = Instant::now();
+                for peer in peer_table.lapsed(now) {
+                    lookup_presence(&mut swarm, &network, &mut presence_lookups, peer);
+                }
 
-                    let dead_peers: Vec<PeerId> = peers_last_seen.iter()
-                        .filter(|(_, &last_seen)| now.duration_since(last_seen) > timeout)
-                        .map(|(&id, _)| id)
-                        .collect();
+                // Status of peers that came online through a connection or gossip
+                for peer in peer_table.presence_unknown() {
+                    lookup_presence(&mut swarm, &network, &mut presence_lookups, peer);
+                }
 
-                    for peer_id in dead_peers {
-                        warn!("Peer timed out (Zombie): {}", peer_id);
-                        if peers_last_seen.remove(&peer_id).is_some() {
-                            let _ = sink.add(format!("PEER-:{}", peer_id));
-                        }
+                // Hole punching retries: a fresh relayed connection makes the other side start DCUtR again.
+                // Retries that come due in the background wait for the foreground
+                if !power_mode.active_discovery() {
+                    continue;
+                }
+                let due = HOLE_PUNCHING.lock().unwrap().as_mut().map(|h| h.due_retries(now)).unwrap_or_default();
+                for peer in due {
+                    let path = CONNECTION_PATHS.lock().unwrap().as_ref().and_then(|paths| paths.get(&peer));
+                    // Direct already or upgrading. A disconnected peer is dialed through the relay again
+                    if matches!(path, Some(PathState::Direct | PathState::HolePunching)) {
+                        continue;
+                    }
+                    let circuits = relay_circuits(&relays, &relay_listen_addrs, peer);
+                    info!("[DCUTR] Retrying hole punch with {} ({} circuit addresses)", peer, circuits.len());
+                    let opts = DialOpts::peer_id(peer).condition(PeerCondition::Always).addresses(circuits).build();
+                    if let Err(e) = swarm.dial(opts) {
+                        warn!("[DCUTR] Retry dial to {} failed: {:?}", peer, e);
                     }
                 }
             }
@@ -232,16 +931,9 @@ pub async fn start_p2p_node(
                     match event {
                         relay::client::Event::ReservationReqAccepted { .. } => {
                             info!("RELAY: Reservation ACCEPTED! I am now reachable via the server.");
-
-                            // Delay for Gossipsub initialization
-                            let tx_for_task = tx_inner.clone();
-                            tokio::spawn(async move {
-                                tokio::time::sleep(Duration::from_millis(500)).await;
-                                // Send message to ourselves (the event will send it properly)
-                                if let Err(e) = tx_for_task.send(("BROADCAST".to_string(), "ANNOUNCE:PRESENCE".to_string())) {
-                                    error!("Error sending delayed presence: {:?}", e);
-                                }
-                            });
+                            if power_mode.keeps_peers() {
+                                publish_presence(&mut swarm, &presence_keys, &network, &own_presence, share_last_seen);
+                            }
                         },
                         other => {
                             warn!("RELAY Event (Posible Error): {:?}", other);
@@ -251,7 +943,7 @@ pub async fn start_p2p_node(
 
                 // Receive message from Peer
                 SwarmEvent::Behaviour(MyP2PBehaviourEvent::Gossipsub(gossipsub::Event::Message {
-                    propagation_source: peer_id,
+                    propagation_source: _,
                     message_id: _,
                     message,
                 })) => {
@@ -259,59 +951,60 @@ pub async fn start_p2p_node(
 
                     let Some(original_sender) = message.source else {
                         warn!("Received message without source ID (Anonymous): {}", text);
-                        return;
+                        continue;
                     };
 
-                    // Extract relay ID
-                    let Some(relay_id) = relay_peer_id else {
-                        return;
-                    };
+                    // Skip messages coming from a Relay itself
+                    if relays.contains_key(&original_sender) || relay_candidates.contains(&original_sender) {
+                        continue;
+                    }
 
-                    // Early return if the message comes from the Relay itself
-                    if original_sender == relay_id {
-                        return;
+                    // Other peers still forward gossip published by a blocked peer
+                    if block_list.contains(&original_sender) {
+                        debug!("[BLOCK] Dropped message from blocked peer {}", original_sender);
+                        continue;
                     }
 
                     // Log regular messages
                     info!("Message received from Peer {} : {}", original_sender, text);
 
                     // Peer discovery and DCUTR upgrade
-                    if peers_last_seen.insert(original_sender, Instant::now()).is_none() {
+                    if peer_table.mark_online(original_sender, DiscoverySource::Gossip, Instant::now() + PRESENCE_TTL) {
                         info!("New peer discovered via Gossipsub: {}", original_sender);
-                        let _ = sink.add(format!("PEER+:{}", original_sender));
+                        let _ = sink.add(peer_joined_event(&original_sender, DiscoverySource::Gossip));
 
-                        // Attempt to upgrade to a direct connection via Relay Circuit
-                        if let Some(relay_addr) = &relay_address_to_dial {
-                            info!("Dialing via Circuit to trigger DCUTR for peer: {}", original_sender);
-
-                            // Build hierarchical address: [Physical] -> [Relay ID] -> [Circuit] -> [Peer ID]
-                            let base_physical = relay_addr.iter()
-                                .filter(|p| matches!(p, Protocol::Ip4(_) | Protocol::Tcp(_) | Protocol::Udp(_)))
-                                .collect::<libp2p::Multiaddr>();
-
-                            let circuit_addr = base_physical
-                                .with(Protocol::P2p(relay_id))
-                                .with(Protocol::P2pCircuit)
-                                .with(Protocol::P2p(original_sender));
+                        // Publicly reachable: a direct dial is enough, private peers dial us back
+                        if nat_status.is_public() {
+                            info!("Publicly reachable, dialing {} directly", original_sender);
+                            if let Err(e) = swarm.dial(original_sender) {
+                                debug!("No direct address for {} yet: {:?}", original_sender, e);
+                            }
+                        }
 
-                            debug!("[DEBUG] Full Circuit Address: {}", circuit_addr);
+                        // Attempt to upgrade to a direct connection via Relay Circuit
+                        if !nat_status.is_public() && !relays.is_empty() {
+                            let circuits = relay_circuits(&relays, &relay_listen_addrs, original_sender);
+                            info!("Dialing via Circuit to trigger DCUTR for peer: {} ({} addresses)", original_sender, circuits.len());
+                            debug!("[DEBUG] Circuit Addresses: {:?}", circuits);
 
-                            if let Err(e) = swarm.dial(circuit_addr) {
+                            let opts = DialOpts::peer_id(original_sender).addresses(circuits).build();
+                            if let Err(e) = swarm.dial(opts) {
                                 warn!("Failed to dial peer via relay circuit: {:?}", e);
                             }
                         }
                     }
 
-                    // Handshake and chat
-                    if text.starts_with("ANNOUNCE:PRESENCE") || text.starts_with("ANNOUNCE:REFRESH") {
-                        info!("Presence signal from {}. Sending WELCOME back.", original_sender);
-                        let topic = gossipsub::IdentTopic::new("p2p-chat-global");
-                        let msg = "ANNOUNCE:WELCOME";
-                        if let Err(e) = swarm.behaviour_mut().gossipsub.publish(topic, msg.as_bytes()) {
-                            warn!("Error sending welcome: {:?}", e);
+                    // Status change of a connected peer, signed by gossipsub like any message
+                    if let Some(status) = text.strip_prefix("ANNOUNCE:STATUS:") {
+                        if let Some(presence) = Presence::parse(status) {
+                            if peer_table.set_presence(&original_sender, presence.clone()) {
+                                info!("[PRESENCE] {} is now {}", original_sender, presence);
+                                let _ = sink.add(status_event(&original_sender, &presence));
+                            }
                         }
-                    } else if text.starts_with("ANNOUNCE:WELCOME") {
-                        info!("Peer {} welcomed us. Connection established.", original_sender);
+                    // Presence moved to DHT records, announcements of older versions are not answered
+                    } else if text.starts_with("ANNOUNCE:") {
+                        debug!("Presence announcement from {}: {}", original_sender, text);
                     } else {
                         // Regular chat message
                         let _ = sink.add(format!("MSG:{}:{}", original_sender, text));
@@ -321,22 +1014,43 @@ pub async fn start_p2p_node(
                 // Peer discovered (mDNS)
                 SwarmEvent::Behaviour(MyP2PBehaviourEvent::Mdns(MdnsEvent::Discovered(list))) => {
                     for (peer_id, multiaddr) in list {
-                        if peers_last_seen.insert(peer_id, Instant::now()).is_none() {
+                        if foreign_peers.contains(&peer_id) || block_list.contains(&peer_id) {
+                            continue;
+                        }
+                        if peer_table.mark_online(peer_id, DiscoverySource::Mdns, Instant::now() + PRESENCE_TTL) {
                             swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                            address_book.add_address(peer_id, &multiaddr);
                             swarm.behaviour_mut().kademlia.add_address(&peer_id, multiaddr);
                             info!("New peer discovered via mDNS: {}", peer_id);
-                            let _ = sink.add(format!("PEER+:{}", peer_id));
+                            let _ = sink.add(peer_joined_event(&peer_id, DiscoverySource::Mdns));
                         }
                     }
                 },
 
                 // Peer discovered (Kademlia)
-                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Kademlia(libp2p::kad::Event::RoutingUpdated { peer, .. })) => {
-                    swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer);
-                    if Some(peer) != relay_peer_id {
-                        if peers_last_seen.insert(peer, Instant::now()).is_none() {
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Kademlia(libp2p::kad::Event::RoutingUpdated { peer, addresses, old_peer, .. })) => {
+                    // A full bucket evicted another peer
+                    if let Some(old_peer) = old_peer {
+                        if let Some(entry) = peer_table.remove(&old_peer, DepartureReason::Unroutable) {
+                            let seen = record_departure(&old_peer, &entry);
+                            let _ = sink.add(peer_left_event(&old_peer, seen, DepartureReason::Unroutable));
+                        }
+                    }
+                    if block_list.contains(&peer) {
+                        swarm.behaviour_mut().kademlia.remove_peer(&peer);
+                        continue;
+                    }
+                    for addr in addresses.iter() {
+                        address_book.add_address(peer, addr);
+                    }
+                    // Gossipsub redials explicit peers on every heartbeat, Suspended keeps only the relays
+                    if power_mode.keeps_peers() || relays.contains_key(&peer) {
+                        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer);
+                    }
+                    if !relays.contains_key(&peer) && !relay_candidates.contains(&peer) {
+                        if peer_table.mark_online(peer, DiscoverySource::Kademlia, Instant::now() + PRESENCE_TTL) {
                             info!("New peer discovered via Kademlia: {}", peer);
-                            let _ = sink.add(format!("PEER+:{}", peer));
+                            let _ = sink.add(peer_joined_event(&peer, DiscoverySource::Kademlia));
                         }
                     } else {
                         info!("Connection opened with Relay Server using Kademlia (Hidden from UI)");
@@ -344,27 +1058,31 @@ pub async fn start_p2p_node(
                 },
 
                 // Any connection
-                SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
-                    swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, .. } => {
+                    if power_mode.keeps_peers() || relays.contains_key(&peer_id) {
+                        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+                    }
                     let remote_addr = endpoint.get_remote_address();
-                    if Some(peer_id) == relay_peer_id {
-                        info!("[NETWORK] Connected to Relay Server, requesting reservation...");
-                        // Request reservation
-                        if let Some(base_addr) = &relay_address_to_dial {
-                            // We verify if the address already has p2p-circuit. If not, we append it.
-                            let mut listen_addr = base_addr.clone();
-                            if !listen_addr.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
-                                listen_addr.push(Protocol::P2pCircuit);
-                            }
-
-                            info!("[RELAY] Sending reservation request via: {}", listen_addr);
-
-                            if let Err(e) = swarm.listen_on(listen_addr) {
-                                error!("[NETWORK] Failed to request Relay Reservation: {:?}", e);
-                            }
+                    address_book.record_success(peer_id, endpoint.is_dialer().then_some(remote_addr));
+                    supervisor.on_connected(&peer_id);
+                    report_connectivity(&sink, &supervisor, &mut last_connectivity);
+                    if let Some(relay_addr) = relays.get(&peer_id).cloned() {
+                        if !relay_needed(&nat_status, &port_mapping) {
+                            info!("[NETWORK] Connected to Relay Server, reachable directly so no reservation needed");
+                        } else {
+                            info!("[NETWORK] Connected to Relay Server, requesting reservation...");
+                            request_reservation(&mut swarm, &mut relay_listeners, peer_id, &relay_addr);
+                        }
+                        sync_rendezvous(&mut swarm, &relays, &mut rendezvous_state);
+                    } else if relay_candidates.contains(&peer_id) {
+                        // Remember where the candidate relay was reached, RTT comes from ping
+                        if endpoint.is_dialer() {
+                            let addr = remote_addr.clone().with(Protocol::P2p(peer_id));
+                            relay_candidates.set_address(&peer_id, addr);
                         }
                     } else {
-                        let connection_type = if remote_addr.to_string().contains("p2p-circuit") {
+                        let relayed = remote_addr.to_string().contains("p2p-circuit");
+                        let connection_type = if relayed {
                             "RELAYED"
                         } else {
                             "DIRECT"
@@ -373,28 +1091,291 @@ pub async fn start_p2p_node(
                         info!("[NETWORK] Connection [{}] established with PEER: {}", connection_type, peer_id);
                         info!("[NETWORK] Address: {}", remote_addr);
 
-                        if remote_addr.to_string().contains("p2p-circuit") {
+                        if relayed && direct {
                             info!("[DCUTR] Relayed connection detected. DCUTR should start now...");
                         }
 
-                        if peers_last_seen.insert(peer_id, Instant::now()).is_none() {
-                            let _ = sink.add(format!("PEER+:{}", peer_id));
+                        let path = CONNECTION_PATHS.lock().unwrap().as_mut()
+                            .and_then(|paths| paths.on_established(peer_id, connection_id, remote_addr, direct));
+                        if let Some(state) = path {
+                            let _ = sink.add(path_event(&peer_id, Some(state)));
+                        }
+                        if relayed && direct {
+                            if let Some(hole_punching) = HOLE_PUNCHING.lock().unwrap().as_mut() {
+                                hole_punching.on_started(peer_id, nat_status_str(&nat_status), transport_name(remote_addr));
+                            }
+                        }
+
+                        if peer_table.mark_online(peer_id, DiscoverySource::Connection, Instant::now() + PRESENCE_TTL) {
+                            let _ = sink.add(peer_joined_event(&peer_id, DiscoverySource::Connection));
                         }
                     }
                 },
 
+                // One of several connections closed, the path may fall back to the relay
+                SwarmEvent::ConnectionClosed { peer_id, connection_id, num_established: 1.., .. } => {
+                    let path = CONNECTION_PATHS.lock().unwrap().as_mut()
+                        .and_then(|paths| paths.on_closed(&peer_id, connection_id));
+                    if let Some(state) = path {
+                        info!("[NETWORK] Connection path to {} is now {}", peer_id, state.as_str());
+                        let _ = sink.add(path_event(&peer_id, Some(state)));
+                    }
+                },
+
                 // Peer disconnected
-                SwarmEvent::ConnectionClosed { peer_id, num_established, .. } => {
-                    if num_established == 0 {
-                        info!("Connection closed with {peer_id}");
-                        // Update Flutter
-                        if peers_last_seen.remove(&peer_id).is_some() {
-                            info!("Peer disconnected: {}", peer_id);
-                            let _ = sink.add(format!("PEER-:{peer_id}"));
+                SwarmEvent::ConnectionClosed { peer_id, connection_id, num_established: 0, .. } => {
+                    info!("Connection closed with {peer_id}");
+                    // Last connection gone, the peer has no path any more
+                    let had_path = CONNECTION_PATHS.lock().unwrap().as_mut().is_some_and(|paths| {
+                        let known = paths.get(&peer_id).is_some();
+                        paths.on_closed(&peer_id, connection_id);
+                        known
+                    });
+                    if had_path {
+                        let _ = sink.add(path_event(&peer_id, None));
+                    }
+                    // The attempt in flight is lost, a scheduled retry still reconnects through a relay
+                    if let Some(hole_punching) = HOLE_PUNCHING.lock().unwrap().as_mut() {
+                        hole_punching.abandon(&peer_id);
+                    }
+                    // Update Flutter, unless another source still vouches for the peer
+                    if let Some(entry) = peer_table.remove(&peer_id, DepartureReason::Closed) {
+                        info!("Peer disconnected: {}", peer_id);
+                        let seen = record_departure(&peer_id, &entry);
+                        let _ = sink.add(peer_left_event(&peer_id, seen, DepartureReason::Closed));
+                    } else if let Some(entry) = peer_table.get(&peer_id) {
+                        debug!("Peer {} disconnected, still online via {:?}", peer_id, entry.sources);
+                    }
+
+                    // Clear Gossipsub
+                    swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                    rendezvous_state.forget_point(&peer_id);
+
+                    // Schedule a redial for relay and bootstrap peers
+                    if let Some(delay) = supervisor.on_disconnected(&peer_id) {
+                        warn!("[RECONNECT] Lost {:?} peer {}, redialing in {:?}", supervisor.kind(&peer_id), peer_id, delay);
+                        report_connectivity(&sink, &supervisor, &mut last_connectivity);
+                    }
+                },
+
+                // Relay circuit listener gone (reservation lost)
+                SwarmEvent::ListenerClosed { listener_id, reason, .. } if relay_listeners.values().any(|id| *id == listener_id) => {
+                    warn!("[RELAY] Circuit listener closed: {:?}", reason);
+                    relay_listeners.retain(|_, id| *id != listener_id);
+                },
+
+                // Reachable through a relay circuit (or directly): we can register now
+                SwarmEvent::ExternalAddrConfirmed { address } => {
+                    info!("[NETWORK] External address confirmed: {}", address);
+                    sync_rendezvous(&mut swarm, &relays, &mut rendezvous_state);
+                },
+
+                // Rendezvous registrations and discoveries
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Rendezvous(event)) => {
+                    match event {
+                        rendezvous::client::Event::Registered { rendezvous_node, ttl, namespace } => {
+                            info!("[RENDEZVOUS] Registered '{}' at {} for {}s", namespace, rendezvous_node, ttl);
+                            rendezvous_state.on_registered(rendezvous_node, namespace, ttl);
+                        },
+                        rendezvous::client::Event::RegisterFailed { rendezvous_node, namespace, error } => {
+                            warn!("[RENDEZVOUS] Registering '{}' at {} failed: {:?}", namespace, rendezvous_node, error);
+                            rendezvous_state.on_register_failed(rendezvous_node, namespace);
+                        },
+                        rendezvous::client::Event::Discovered { rendezvous_node, registrations, cookie } => {
+                            rendezvous_state.set_cookie(rendezvous_node, cookie);
+                            for registration in registrations {
+                                let peer = registration.record.peer_id();
+                                if peer == *swarm.local_peer_id() || relays.contains_key(&peer)
+                                    || block_list.contains(&peer) || foreign_peers.contains(&peer)
+                                {
+                                    continue;
+                                }
+                                let addrs = registration.record.addresses().to_vec();
+                                for addr in &addrs {
+                                    address_book.add_address(peer, addr);
+                                    swarm.behaviour_mut().kademlia.add_address(&peer, addr.clone());
+                                }
+                                if rendezvous_state.add_member(&registration.namespace, peer) {
+                                    if let Some(room) = rendezvous_state.room(&registration.namespace) {
+                                        info!("[RENDEZVOUS] Peer {} is in room '{}'", peer, room);
+                                        let _ = sink.add(format!("ROOM+:{}:{}", room, peer));
+                                    }
+                                }
+                                if power_mode.keeps_peers() && !swarm.is_connected(&peer) {
+                                    debug!("[RENDEZVOUS] Dialing {} discovered at {}", peer, rendezvous_node);
+                                    if let Err(e) = swarm.dial(DialOpts::peer_id(peer).addresses(addrs).build()) {
+                                        debug!("[RENDEZVOUS] Could not dial {}: {:?}", peer, e);
+                                    }
+                                }
+                            }
+                        },
+                        rendezvous::client::Event::DiscoverFailed { rendezvous_node, namespace, error } => {
+                            warn!("[RENDEZVOUS] Discovery of {:?} at {} failed: {:?}", namespace, rendezvous_node, error);
+                        },
+                        rendezvous::client::Event::Expired { peer } => {
+                            debug!("[RENDEZVOUS] Registration of {} expired", peer);
+                        },
+                    }
+                },
+
+                // Reachability changed
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Autonat(autonat::Event::StatusChanged { old, new })) => {
+                    info!("[AUTONAT] Reachability changed: {:?} -> {:?}", old, new);
+                    // Public serves the DHT, Private is a client, Unknown lets Kademlia decide
+                    let mode = match &new {
+                        autonat::NatStatus::Public(_) => Some(KademliaMode::Server),
+                        autonat::NatStatus::Private => Some(KademliaMode::Client),
+                        autonat::NatStatus::Unknown => None,
+                    };
+                    swarm.behaviour_mut().kademlia.set_mode(mode);
+                    nat_status = new;
+                    update_reservations(&mut swarm, &relays, &mut relay_listeners, relay_needed(&nat_status, &port_mapping));
+                    let _ = sink.add(format!("NAT:{}", nat_status_str(&nat_status)));
+                },
+
+                // UPnP port mapping
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Upnp(event)) => {
+                    match &event {
+                        upnp::Event::NewExternalAddr(addr) => info!("[UPNP] Port mapped, external address: {}", addr),
+                        upnp::Event::ExpiredExternalAddr(addr) => warn!("[UPNP] Port mapping expired: {}", addr),
+                        upnp::Event::GatewayNotFound => warn!("[UPNP] No gateway found, falling back to relay"),
+                        upnp::Event::NonRoutableGateway => warn!("[UPNP] Gateway is not public, falling back to relay"),
+                    }
+                    if port_mapping.on_event(&event) {
+                        update_reservations(&mut swarm, &relays, &mut relay_listeners, relay_needed(&nat_status, &port_mapping));
+                    }
+                    let _ = sink.add(format!("UPNP:{}", port_mapping.as_str()));
+                },
+
+                // Inbound records, stored only if valid
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Kademlia(libp2p::kad::Event::InboundRequest { request })) => {
+                    match request {
+                        InboundRequest::PutRecord { source, record: Some(record), .. } => {
+                            if !accept_record(&network, &record) {
+                                warn!("[PRESENCE] Dropped invalid presence record from {}", source);
+                                continue;
+                            }
+                            if let Err(e) = swarm.behaviour_mut().kademlia.store_mut().put(record) {
+                                debug!("[DHT] Record from {} not stored: {:?}", source, e);
+                            }
+                        },
+                        InboundRequest::AddProvider { record: Some(record) } => {
+                            if let Err(e) = swarm.behaviour_mut().kademlia.store_mut().add_provider(record) {
+                                debug!("[DHT] Provider record not stored: {:?}", e);
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+
+                // Presence lookups
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Kademlia(libp2p::kad::Event::OutboundQueryProgressed {
+                    id, result: QueryResult::GetRecord(result), step, ..
+                })) if presence_lookups.contains_key(&id) => {
+                    if let Ok(GetRecordOk::FoundRecord(PeerRecord { record, .. })) = result {
+                        let peer = presence_lookups[&id].0;
+                        match verify_presence(&network, &record) {
+                            // An invisible peer's record only updates the status of a peer we already know
+                            Some(verified) if verified.peer == peer && verified.presence.status == PresenceStatus::Invisible => {
+                                if peer_table.set_presence(&peer, verified.presence.clone()) {
+                                    info!("[PRESENCE] {} went invisible", peer);
+                                    let _ = sink.add(status_event(&peer, &verified.presence));
+                                }
+                                if let Some(mut query) = swarm.behaviour_mut().kademlia.query_mut(&id) {
+                                    query.finish();
+                                }
+                            },
+                            Some(verified) if verified.peer == peer && !block_list.contains(&peer) => {
+                                presence_lookups.insert(id, (peer, true));
+                                if peer_table.mark_online(peer, DiscoverySource::Presence, verified.expires) {
+                                    info!("[PRESENCE] {} is online", peer);
+                                    let _ = sink.add(peer_joined_event(&peer, DiscoverySource::Presence));
+                                }
+                                if peer_table.set_presence(&peer, verified.presence.clone()) {
+                                    let _ = sink.add(status_event(&peer, &verified.presence));
+                                }
+                                peer_table.set_shares_last_seen(&peer, verified.share_last_seen);
+                                if !verified.share_last_seen {
+                                    if let Some(last_seen) = LAST_SEEN.lock().unwrap().as_mut() {
+                                        last_seen.forget(&peer);
+                                        last_seen.save_if_dirty();
+                                    }
+                                }
+                                if let Some(mut query) = swarm.behaviour_mut().kademlia.query_mut(&id) {
+                                    query.finish();
+                                }
+                            },
+                            Some(_) => {},
+                            None => debug!("[PRESENCE] Ignoring invalid or expired presence record of {}", peer),
                         }
+                    }
+                    if step.last {
+                        let (peer, found) = presence_lookups.remove(&id).expect("Checked by the guard");
+                        if !found && !swarm.is_connected(&peer) {
+                            if let Some(entry) = peer_table.remove(&peer, DepartureReason::Timeout) {
+                                info!("[PRESENCE] Presence of {} expired", peer);
+                                let seen = record_departure(&peer, &entry);
+                                let _ = sink.add(peer_left_event(&peer, seen, DepartureReason::Timeout));
+                            }
+                        }
+                    }
+                },
 
-                        // Clear Gossipsub
-                        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                // Relay candidates found in the DHT
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Kademlia(libp2p::kad::Event::OutboundQueryProgressed {
+                    result: libp2p::kad::QueryResult::GetProviders(Ok(libp2p::kad::GetProvidersOk::FoundProviders { providers, .. })),
+                    ..
+                })) => {
+                    for provider in providers {
+                        if provider == *swarm.local_peer_id() || relays.contains_key(&provider) {
+                            continue;
+                        }
+                        if relay_candidates.add(provider) {
+                            info!("[RELAY] Discovered relay candidate via DHT: {}", provider);
+                            // Addresses are known to the running query, dial by PeerId
+                            if let Err(e) = swarm.dial(DialOpts::peer_id(provider).build()) {
+                                warn!("[RELAY] Error dialing relay candidate {}: {:?}", provider, e);
+                                relay_candidates.record_failure(&provider);
+                            }
+                        }
+                    }
+                },
+
+                // RTT of relay candidates
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Ping(ping::Event { peer, result, .. })) if relay_candidates.contains(&peer) => {
+                    match result {
+                        Ok(rtt) => {
+                            debug!("[RELAY] Candidate {} RTT: {:?}", peer, rtt);
+                            relay_candidates.record_rtt(&peer, rtt);
+                        },
+                        Err(e) => {
+                            warn!("[RELAY] Ping to candidate {} failed: {:?}", peer, e);
+                            relay_candidates.record_failure(&peer);
+                        }
+                    }
+                },
+
+                // Liveness of online peers. Pings keep failing on a connection that died silently,
+                // so after a few in a row the peer is dropped without waiting for its presence to expire
+                SwarmEvent::Behaviour(MyP2PBehaviourEvent::Ping(ping::Event { peer, result, .. })) if peer_table.contains(&peer) => {
+                    let entry = match result {
+                        Ok(rtt) => peer_table.record_rtt(&peer, rtt),
+                        Err(e) => {
+                            debug!("[PING] Ping to {} failed: {:?}", peer, e);
+                            peer_table.record_ping_failure(&peer)
+                        },
+                    };
+                    let Some(entry) = entry else {
+                        continue;
+                    };
+                    let _ = sink.add(ping_event(&peer, entry));
+                    if entry.consecutive_failures >= MAX_PING_FAILURES {
+                        warn!("[PING] {} failed {} pings in a row, dropping it", peer, entry.consecutive_failures);
+                        if let Some(entry) = peer_table.remove(&peer, DepartureReason::Unresponsive) {
+                            let seen = record_departure(&peer, &entry);
+                            let _ = sink.add(peer_left_event(&peer, seen, DepartureReason::Unresponsive));
+                        }
+                        let _ = swarm.disconnect_peer_id(peer);
                     }
                 },
 
@@ -402,9 +1383,10 @@ pub async fn start_p2p_node(
                 SwarmEvent::Behaviour(MyP2PBehaviourEvent::Mdns(MdnsEvent::Expired(list))) => {
                     for (peer_id, multiaddr) in list {
                         swarm.behaviour_mut().kademlia.remove_address(&peer_id, &multiaddr);
-                        if peers_last_seen.remove(&peer_id).is_some() {
+                        if let Some(entry) = peer_table.remove(&peer_id, DepartureReason::MdnsExpired) {
                             info!("mDNS expired for {}: address {} removed form routing table", peer_id, multiaddr);
-                            let _ = sink.add(format!("PEER-:{}", peer_id));
+                            let seen = record_departure(&peer_id, &entry);
+                            let _ = sink.add(peer_left_event(&peer_id, seen, DepartureReason::MdnsExpired));
                         }
                     }
                 },
@@ -412,7 +1394,25 @@ pub async fn start_p2p_node(
                 // Info of Peer
                 SwarmEvent::Behaviour(MyP2PBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. })) => {
                     info!("Identify: info of Peer {peer_id}");
+                    if info.protocol_version != network.identify_protocol() {
+                        warn!("[NETWORK] Peer {} belongs to another network ({}), disconnecting", peer_id, info.protocol_version);
+                        foreign_peers.insert(peer_id);
+                        address_book.remove(&peer_id);
+                        swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+                        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
+                        if let Some(entry) = peer_table.remove(&peer_id, DepartureReason::Foreign) {
+                            let seen = record_departure(&peer_id, &entry);
+                            let _ = sink.add(peer_left_event(&peer_id, seen, DepartureReason::Foreign));
+                        }
+                        let _ = swarm.disconnect_peer_id(peer_id);
+                        continue;
+                    }
+                    if relays.contains_key(&peer_id) {
+                        let addrs = info.listen_addrs.iter().filter(|a| is_dialable_relay_address(a)).cloned().collect();
+                        relay_listen_addrs.insert(peer_id, addrs);
+                    }
                     for addr in info.listen_addrs {
+                        address_book.add_address(peer_id, &addr);
                         swarm.behaviour_mut().kademlia.add_address(&peer_id, addr);
                     }
                 },
@@ -420,14 +1420,31 @@ pub async fn start_p2p_node(
                 // DCUTR
                 SwarmEvent::Behaviour(MyP2PBehaviourEvent::DcutrHandler(dcutr_event)) => {
                     let peer = dcutr_event.remote_peer_id;
+                    let success = dcutr_event.result.is_ok();
+                    let mut paths = CONNECTION_PATHS.lock().unwrap();
+                    let mut hole_punching = HOLE_PUNCHING.lock().unwrap();
+                    let (Some(paths), Some(hole_punching)) = (paths.as_mut(), hole_punching.as_mut()) else {
+                        continue;
+                    };
                     match dcutr_event.result {
                         Ok(connection_id) => {
-                            info!("[DCUTR] Direct connection with Peer {peer} (connection_id: {:?})", connection_id);
+                            let transport = paths.transport(&peer, connection_id).unwrap_or("OTHER");
+                            let took = hole_punching.on_succeeded(&peer, transport);
+                            info!("[DCUTR] Direct connection with Peer {peer} over {} after {:?} (connection_id: {:?})", transport, took, connection_id);
                         },
                         Err(e) => {
-                            warn!("[DCUTR] Could not upgrade connection with {}: {:?}", peer, e);
+                            let (took, retry) = hole_punching.on_failed(peer);
+                            warn!("[DCUTR] Could not upgrade connection with {} after {:?}: {:?}", peer, took, e);
+                            match retry {
+                                Some(delay) => info!("[DCUTR] Retrying {} in {:?}", peer, delay),
+                                None => warn!("[DCUTR] Giving up on {} after {} retries, staying on the relay", peer, MAX_HOLE_PUNCH_RETRIES),
+                            }
                         }
                     }
+                    let path = paths.on_upgrade(&peer, success);
+                    if let Some(state) = path {
+                        let _ = sink.add(path_event(&peer, Some(state)));
+                    }
                 },
 
                 // Other events for debugging
@@ -435,10 +1452,29 @@ pub async fn start_p2p_node(
                     debug!("DEBUG: Behavior Event: {:?}", other_event);
                 },
 
-                SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
-                    if let Some(pid) = peer_id {
+                SwarmEvent::IncomingConnectionError { send_back_addr, error, .. } => {
+                    if let Some(reason) = limit_metrics.record_incoming(&error) {
+                        warn!("[LIMITS] Rejected incoming connection from {}: {}", send_back_addr, reason);
+                        continue;
+                    }
+                    warn!("[NETWORK] Incoming connection from {} failed: {:?}", send_back_addr, error);
+                },
+
+                SwarmEvent::OutgoingConnectionError { peer_id: Some(pid), error, .. } => {
+                    if let Some(reason) = limit_metrics.record_outgoing(&error) {
+                        warn!("[LIMITS] Dial to {} refused: {}", pid, reason);
+                    } else {
                         error!("[NETWORK] Dial error to {}: {:?}", pid, error);
                     }
+                    if !relays.contains_key(&pid) {
+                        relay_candidates.record_failure(&pid);
+                    }
+                    if !swarm.is_connected(&pid) {
+                        if let Some(delay) = supervisor.on_disconnected(&pid) {
+                            warn!("[RECONNECT] Dial to {} failed, retrying in {:?}", pid, delay);
+                            report_connectivity(&sink, &supervisor, &mut last_connectivity);
+                        }
+                    }
                 },
 
                 _ => {}
@@ -447,9 +1483,100 @@ pub async fn start_p2p_node(
     }
 }
 
+// FOREGROUND, BACKGROUND (slower discovery, no mDNS or random walks) or SUSPENDED
+// (only the relay reservation is kept, presence expires). Going back to FOREGROUND catches up
+#[frb(sync)]
+pub fn set_power_mode(mode: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("POWER".to_string(), mode));
+    }
+}
+
+// The device switched networks (e.g. Wi-Fi to mobile data): listen again, reconnect to the relays,
+// renew the reservation and republish our presence. Desktops detect this on their own
+#[frb(sync)]
+pub fn notify_network_changed() {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("NETWORK_CHANGED".to_string(), String::new()));
+    }
+}
+
+// Look up whether a peer is online, answered with PEER+ (or PEER- if it went away)
+#[frb(sync)]
+pub fn check_presence(peer_id: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("PRESENCE".to_string(), peer_id));
+    }
+}
+
+// Hole punching attempts, successes, failures and average time in total, by NAT status and by transport, as JSON
+#[frb(sync)]
+pub fn get_hole_punch_stats() -> String {
+    let hole_punching = HOLE_PUNCHING.lock().unwrap();
+    let summary = hole_punching.as_ref().map(|h| h.summary().clone()).unwrap_or_default();
+    serde_json::to_string(&summary).unwrap_or_default()
+}
+
+// How a connected peer is reached (RELAYED, HOLE_PUNCHING, DIRECT, FAILED), empty if not connected
+#[frb(sync)]
+pub fn get_connection_path(peer_id: String) -> String {
+    let Ok(peer) = peer_id.parse::<PeerId>() else {
+        return String::new();
+    };
+    let paths = CONNECTION_PATHS.lock().unwrap();
+    paths.as_ref().and_then(|paths| paths.get(&peer)).map(|state| state.as_str().to_string()).unwrap_or_default()
+}
+
+// When a contact was last seen online (unix seconds), None if unknown or not shared by the contact
+#[frb(sync)]
+pub fn get_last_seen(peer_id: String) -> Option<u64> {
+    let peer = peer_id.parse::<PeerId>().ok()?;
+    let last_seen = LAST_SEEN.lock().unwrap();
+    last_seen.as_ref()?.get(&peer)
+}
+
+// Our status (ONLINE, AWAY, DND, INVISIBLE) and status line, published with our presence record.
+// Invisible peers stay connected and keep receiving messages, others are told to show them offline
+#[frb(sync)]
+pub fn set_presence(status: String, text: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("STATUS".to_string(), format!("{}:{}", status, text)));
+    }
+}
+
+// Rooms are rendezvous namespaces, members show up as ROOM+:<room>:<peer>
+#[frb(sync)]
+pub fn join_room(room: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("JOIN_ROOM".to_string(), room));
+    }
+}
+
+#[frb(sync)]
+pub fn leave_room(room: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("LEAVE_ROOM".to_string(), room));
+    }
+}
+
 #[frb(sync)]
 pub fn refresh_node() {
     if let Some(sender) = COMMAND_SENDER.get() {
         let _ = sender.send(("REFRESH".to_string(), "REFRESH".to_string()));
     }
 }
+
+// Persistently refuse connections and messages from a peer
+#[frb(sync)]
+pub fn block_peer(peer_id: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("BLOCK".to_string(), peer_id));
+    }
+}
+
+#[frb(sync)]
+pub fn unblock_peer(peer_id: String) {
+    if let Some(sender) = COMMAND_SENDER.get() {
+        let _ = sender.send(("UNBLOCK".to_string(), peer_id));
+    }
+}
@@ -1,35 +1,70 @@
+use std::collections::HashSet;
+use std::path::Path;
 use std::time::Duration;
 use futures::StreamExt;
 use libp2p::{
     gossipsub,
-    kad::{store::MemoryStore, Behaviour as Kademlia, Config as KademliaConfig},
+    kad::{store::RecordStore, Behaviour as Kademlia, Config as KademliaConfig, InboundRequest, Mode as KademliaMode, StoreInserts},
     ping::{Behaviour as Ping, Config as PingConfig},
     mdns::{tokio::Behaviour as Mdns, Config as MdnsConfig},
     swarm::{SwarmEvent, NetworkBehaviour},
-    SwarmBuilder, PeerId, identify, relay,
+    SwarmBuilder, PeerId, identify, relay, autonat, allow_block_list, connection_limits, memory_connection_limits,
+    rendezvous,
+    swarm::behaviour::toggle::Toggle,
 };
-use p2p_core::identity::get_or_create_identity;
-use p2p_core::transport::build_transport;
+use p2p_core::identity::{get_or_create_identity, get_or_create_webrtc_certificate, load_pre_shared_key};
+use p2p_core::transport::{build_transport, DnsFallback, TransportConfig};
 use p2p_core::logger::init_p2p_logging;
+use p2p_core::network::Network;
+use p2p_core::limits::{LimitMetrics, Limits};
+use p2p_core::block_list::BlockList;
+use p2p_core::record_store::{DhtStore, DhtStoreConfig, DHT_SAVE_INTERVAL};
+use p2p_core::presence::accept_record;
+use p2p_core::rendezvous::MIN_REGISTRATION_TTL;
 use tracing::{info, warn};
 
+// Re-announce the relay provider record so new DHT nodes learn about it
+const PROVIDER_REFRESH: Duration = Duration::from_secs(600);
+// How often connection, circuit and rejection counters are logged
+const METRICS_INTERVAL: Duration = Duration::from_secs(60);
+// How often the block list file is checked for manual edits
+const BLOCK_LIST_RELOAD: Duration = Duration::from_secs(10);
+
 // Relay behaviour
 #[derive(NetworkBehaviour)]
 struct RelayBehaviour {
+    // Block list and limits first, so a refused connection never reaches the other behaviours
+    blocked_peers: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+    connection_limits: connection_limits::Behaviour,
+    memory_limits: Toggle<memory_connection_limits::Behaviour>,
     gossipsub: gossipsub::Behaviour,
-    kademlia: Kademlia<MemoryStore>,
+    kademlia: Kademlia<DhtStore>,
     ping: Ping,
     mdns: Mdns,
     identify: identify::Behaviour,
     relay: relay::Behaviour,
+    autonat: autonat::Behaviour,
+    rendezvous: rendezvous::server::Behaviour,
+}
+
+// Refuse connections from the peer (closing open ones), stop relaying its gossip and drop it from the DHT
+fn block_peer(swarm: &mut libp2p::Swarm<RelayBehaviour>, peer: PeerId) {
+    let behaviour = swarm.behaviour_mut();
+    behaviour.blocked_peers.block_peer(peer);
+    behaviour.gossipsub.blacklist_peer(&peer);
+    behaviour.kademlia.remove_peer(&peer);
 }
 
 #[tokio::main]
 async fn main() {
     // Server configuration
     let listen_port = 4001;
+    let ws_port = 443;
+    let webrtc_port = 4002;
     let storage_path = "./";
     let instance_name = "oracle_relay_v1";
+    // Staging and production relays can share a host, P2P_NETWORK_ID keeps them apart
+    let network = Network::new(&std::env::var("P2P_NETWORK_ID").unwrap_or_default()).unwrap_or_else(|e| panic!("{}", e));
 
     // Identity
     let id_keys = get_or_create_identity(storage_path, instance_name);
@@ -40,15 +75,28 @@ async fn main() {
 
     info!("Starting Relay Server P2P on port {}", listen_port);
     info!("SERVER PEER ID: {}", peer_id);
+    info!("Network: '{}' (topic {})", network.id(), network.topic_name());
 
     // Transport
-    let (transport, _relay_client_transport) = build_transport(&id_keys, peer_id);
+    // WSS if <storage_path>/tls/{cert,key}.pem exist, plain WS otherwise. WebRTC-direct for browsers.
+    // A <storage_path>/swarm.key turns the relay into a private network node
+    // P2P_DNS_FALLBACK picks the resolvers used if the system DNS configuration is unreadable
+    let pre_shared_key = load_pre_shared_key(storage_path, "").unwrap_or_else(|e| panic!("{}", e));
+    let dns_fallback = DnsFallback::parse(&std::env::var("P2P_DNS_FALLBACK").unwrap_or_default()).unwrap_or_else(|e| panic!("{}", e));
+    let transport_config = TransportConfig::default()
+        .with_wss_certs_in(Path::new(storage_path).join("tls"))
+        .with_webrtc(get_or_create_webrtc_certificate(storage_path, instance_name))
+        .with_pre_shared_key(pre_shared_key)
+        .with_dns_fallback(dns_fallback);
+    let (transport, _relay_client_transport) = build_transport(&id_keys, peer_id, &transport_config);
 
     // Behaviour configuration
     let mut kad_config = KademliaConfig::default();
-    kad_config.set_protocol_names(vec![libp2p::StreamProtocol::new("/p2p_msg/kad/1.0.0")]);
+    kad_config.set_protocol_names(vec![network.kad_protocol()]);
+    // Presence records are verified before they are stored and served
+    kad_config.set_record_filtering(StoreInserts::FilterBoth);
 
-    let gossip_config = gossipsub::ConfigBuilder::default()
+    let gossip_config = network.gossipsub_config()
         .heartbeat_interval(Duration::from_secs(1))
         .validation_mode(gossipsub::ValidationMode::Strict)
         .build()
@@ -66,17 +114,47 @@ async fn main() {
         ..Default::default()
     };
 
+    // Raw connection caps, tunable through P2P_MAX_* environment variables
+    let limits = Limits::relay().with_env_overrides();
+    info!("[LIMITS] {}", limits);
+
+    // DHT records (relay provider records included) survive restarts in <storage_path>/dht_<instance>.redb.
+    // P2P_DHT_STORE=memory keeps them in memory only
+    let dht_config = DhtStoreConfig {
+        max_records: 16 * 1024,
+        max_provided_keys: 16 * 1024,
+        ..Default::default()
+    };
+    let dht_store = if std::env::var("P2P_DHT_STORE").is_ok_and(|v| v.eq_ignore_ascii_case("memory")) {
+        DhtStore::in_memory(peer_id, dht_config)
+    } else {
+        DhtStore::persistent(peer_id, Path::new(storage_path).join(format!("dht_{}.redb", instance_name)), dht_config)
+    };
+
     // Relay Behaviour config
     let behaviour = RelayBehaviour {
-        kademlia: Kademlia::with_config(peer_id, MemoryStore::new(peer_id), kad_config),
+        blocked_peers: allow_block_list::Behaviour::default(),
+        connection_limits: limits.connection_limits(),
+        memory_limits: limits.memory_limits(),
+        kademlia: Kademlia::with_config(peer_id, dht_store, kad_config),
         ping: Ping::new(PingConfig::new().with_interval(Duration::from_secs(30))),
         mdns: Mdns::new(MdnsConfig::default(), peer_id).expect("mDNS Error"),
         gossipsub,
         identify: identify::Behaviour::new(identify::Config::new(
-            "/p2p_msg/id/1.0.0".to_string(),
+            network.identify_protocol(),
             id_keys.public()
         )),
         relay: relay::Behaviour::new(peer_id, relay_config),
+        // AutoNAT server: dials back nodes so they learn their reachability
+        autonat: autonat::Behaviour::new(peer_id, autonat::Config {
+            throttle_clients_global_max: 120,
+            throttle_clients_peer_max: 3,
+            ..Default::default()
+        }),
+        // Rendezvous point: nodes register under the network and room namespaces and discover each other here
+        rendezvous: rendezvous::server::Behaviour::new(
+            rendezvous::server::Config::default().with_min_ttl(MIN_REGISTRATION_TTL),
+        ),
     };
 
     // Swarm
@@ -89,19 +167,112 @@ async fn main() {
 
     // Listeners
     let tcp_addr = format!("/ip4/0.0.0.0/tcp/{}", listen_port).parse::<libp2p::Multiaddr>().unwrap();
-    let udp_addr = format!("/ip4/0.0.0.0/udp/{}/quic-v1", listen_port).parse::<libp2p::Multiaddr>().unwrap();
-
     swarm.listen_on(tcp_addr).unwrap();
-    swarm.listen_on(udp_addr).unwrap();
+
+    // QUIC and WebRTC cannot carry the pre-shared key
+    if transport_config.udp_transports_enabled() {
+        let udp_addr = format!("/ip4/0.0.0.0/udp/{}/quic-v1", listen_port).parse::<libp2p::Multiaddr>().unwrap();
+        swarm.listen_on(udp_addr).unwrap();
+    }
+
+    // WebSocket listener for clients behind HTTP(S)-only proxies. Port 443 needs privileges
+    let ws_protocol = if transport_config.wss_enabled() { "wss" } else { "ws" };
+    let ws_addr = format!("/ip4/0.0.0.0/tcp/{}/{}", ws_port, ws_protocol).parse::<libp2p::Multiaddr>().unwrap();
+    if let Err(e) = swarm.listen_on(ws_addr.clone()) {
+        warn!("Failed to listen on {}: {}", ws_addr, e);
+    }
+
+    // WebRTC-direct listener for the web build. The /certhash is logged with the listen address
+    if transport_config.udp_transports_enabled() {
+        let webrtc_addr = format!("/ip4/0.0.0.0/udp/{}/webrtc-direct", webrtc_port).parse::<libp2p::Multiaddr>().unwrap();
+        if let Err(e) = swarm.listen_on(webrtc_addr.clone()) {
+            warn!("Failed to listen on {}: {}", webrtc_addr, e);
+        }
+    }
+
+    // Public addresses put in provider records and reservations: P2P_EXTERNAL_ADDRS (comma separated),
+    // plus the addresses peers observe once AutoNAT confirmed them
+    let external_addrs = std::env::var("P2P_EXTERNAL_ADDRS").unwrap_or_default();
+    for addr in external_addrs.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+        match addr.parse::<libp2p::Multiaddr>() {
+            Ok(addr) => {
+                info!("External address (configured): {}", addr);
+                swarm.add_external_address(addr);
+            },
+            Err(e) => warn!("Invalid address in P2P_EXTERNAL_ADDRS '{}': {}", addr, e),
+        }
+    }
 
     // Subscribe to global topic
-    let topic = gossipsub::IdentTopic::new("p2p-chat-global");
+    let topic = network.topic();
     swarm.behaviour_mut().gossipsub.subscribe(&topic).unwrap();
-    info!("Server subscribed to topic: p2p-chat-global");
+    info!("Server subscribed to topic: {}", network.topic_name());
+
+    // Blocked peers: <storage_path>/blocked_peers_<instance>.txt, one PeerId per line, picked up while running
+    let mut block_list = BlockList::load(storage_path, instance_name);
+    for peer in block_list.peers() {
+        block_peer(&mut swarm, *peer);
+    }
+    let mut block_list_interval = tokio::time::interval(BLOCK_LIST_RELOAD);
+
+    // Serve DHT queries even before an external address is confirmed
+    swarm.behaviour_mut().kademlia.set_mode(Some(KademliaMode::Server));
+    let mut provider_interval = tokio::time::interval(PROVIDER_REFRESH);
+    let mut metrics_interval = tokio::time::interval(METRICS_INTERVAL);
+    let mut dht_save_interval = tokio::time::interval(DHT_SAVE_INTERVAL);
+    let mut limit_metrics = LimitMetrics::default();
+    let mut denied_reservations: u64 = 0;
+    let mut denied_circuits: u64 = 0;
+    let mut registrations: HashSet<(PeerId, rendezvous::Namespace)> = HashSet::new();
 
     // Event loop
     loop {
-        match swarm.select_next_some().await {
+        let event = tokio::select! {
+            event = swarm.select_next_some() => event,
+            _ = provider_interval.tick() => {
+                // Advertise this relay for automatic relay discovery
+                if let Err(e) = swarm.behaviour_mut().kademlia.start_providing(network.relay_provider_key()) {
+                    warn!("Error announcing relay provider record: {:?}", e);
+                } else {
+                    info!("Announced as provider for {}", network.relay_provider_key_name());
+                }
+                continue;
+            }
+            _ = block_list_interval.tick() => {
+                if let Some((blocked, unblocked)) = block_list.reload() {
+                    for peer in blocked {
+                        info!("[BLOCK] Blocking peer {}", peer);
+                        block_peer(&mut swarm, peer);
+                    }
+                    for peer in unblocked {
+                        info!("[BLOCK] Unblocking peer {}", peer);
+                        swarm.behaviour_mut().blocked_peers.unblock_peer(peer);
+                        swarm.behaviour_mut().gossipsub.remove_blacklisted_peer(&peer);
+                    }
+                }
+                continue;
+            }
+            _ = dht_save_interval.tick() => {
+                swarm.behaviour_mut().kademlia.store_mut().save_if_dirty();
+                continue;
+            }
+            _ = metrics_interval.tick() => {
+                let info = swarm.network_info();
+                info!(
+                    "[METRICS] {} peers, {} connections ({} pending), {}, denied reservations {}, denied circuits {}, {} rendezvous registrations",
+                    info.num_peers(),
+                    info.connection_counters().num_established(),
+                    info.connection_counters().num_pending(),
+                    limit_metrics,
+                    denied_reservations,
+                    denied_circuits,
+                    registrations.len(),
+                );
+                continue;
+            }
+        };
+
+        match event {
             SwarmEvent::NewListenAddr { address, .. } => {
                 info!("Listening on: {}", address);
             },
@@ -110,6 +281,14 @@ async fn main() {
                 info!("New incoming connection from: {:?}", send_back_addr);
             },
 
+            SwarmEvent::IncomingConnectionError { send_back_addr, error, .. } => {
+                if let Some(reason) = limit_metrics.record_incoming(&error) {
+                    warn!("[LIMITS] Rejected incoming connection from {}: {}", send_back_addr, reason);
+                    continue;
+                }
+                warn!("Incoming connection from {} failed: {:?}", send_back_addr, error);
+            },
+
             SwarmEvent::ConnectionEstablished { peer_id, .. } => {
                 info!("Connection established with Peer: {}", peer_id);
                 swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
@@ -130,15 +309,47 @@ async fn main() {
                         info!("Circuit created: {} <--> {}", src_peer_id, dst_peer_id);
                     },
                     relay::Event::ReservationReqDenied { src_peer_id } => {
+                        denied_reservations += 1;
                         warn!("Reservation denied for: {}", src_peer_id);
                     },
                     relay::Event::CircuitReqDenied { src_peer_id, dst_peer_id } => {
+                        denied_circuits += 1;
                         warn!("Circuit denied: {} -> {}", src_peer_id, dst_peer_id);
                     },
                     _ => {}
                 }
             },
 
+            // Rendezvous registrations
+            SwarmEvent::Behaviour(RelayBehaviourEvent::Rendezvous(event)) => {
+                match event {
+                    rendezvous::server::Event::PeerRegistered { peer, registration } => {
+                        if registrations.insert((peer, registration.namespace.clone())) {
+                            info!("[RENDEZVOUS] {} registered under '{}' for {}s", peer, registration.namespace, registration.ttl);
+                        }
+                    },
+                    rendezvous::server::Event::PeerNotRegistered { peer, namespace, error } => {
+                        warn!("[RENDEZVOUS] Registration of {} under '{}' refused: {:?}", peer, namespace, error);
+                    },
+                    rendezvous::server::Event::PeerUnregistered { peer, namespace } => {
+                        registrations.remove(&(peer, namespace.clone()));
+                        info!("[RENDEZVOUS] {} unregistered from '{}'", peer, namespace);
+                    },
+                    rendezvous::server::Event::RegistrationExpired(registration) => {
+                        registrations.remove(&(registration.record.peer_id(), registration.namespace));
+                    },
+                    rendezvous::server::Event::DiscoverNotServed { enquirer, error } => {
+                        warn!("[RENDEZVOUS] Discovery by {} not served: {:?}", enquirer, error);
+                    },
+                    rendezvous::server::Event::DiscoverServed { .. } => {},
+                }
+            },
+
+            // AutoNAT dial-back results
+            SwarmEvent::Behaviour(RelayBehaviourEvent::Autonat(autonat::Event::InboundProbe(event))) => {
+                info!("AutoNAT probe: {:?}", event);
+            },
+
             // Message Debugging
             SwarmEvent::Behaviour(RelayBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
                 let text = String::from_utf8_lossy(&message.data);
@@ -147,14 +358,49 @@ async fn main() {
                 }
             },
 
+            // Inbound DHT records, stored only if valid
+            SwarmEvent::Behaviour(RelayBehaviourEvent::Kademlia(libp2p::kad::Event::InboundRequest { request })) => {
+                match request {
+                    InboundRequest::PutRecord { source, record: Some(record), .. } => {
+                        if !accept_record(&network, &record) {
+                            warn!("Dropped invalid presence record from {}", source);
+                            continue;
+                        }
+                        if let Err(e) = swarm.behaviour_mut().kademlia.store_mut().put(record) {
+                            warn!("Record from {} not stored: {:?}", source, e);
+                        }
+                    },
+                    InboundRequest::AddProvider { record: Some(record) } => {
+                        if let Err(e) = swarm.behaviour_mut().kademlia.store_mut().add_provider(record) {
+                            warn!("Provider record not stored: {:?}", e);
+                        }
+                    },
+                    _ => {},
+                }
+            },
+
             // Kademlia / Identify
             SwarmEvent::Behaviour(RelayBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. })) => {
+                if info.protocol_version != network.identify_protocol() {
+                    warn!("Peer {} belongs to another network ({}), disconnecting", peer_id, info.protocol_version);
+                    swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+                    let _ = swarm.disconnect_peer_id(peer_id);
+                    continue;
+                }
                 for addr in info.listen_addrs {
                     swarm.behaviour_mut().kademlia.add_address(&peer_id, addr);
                 }
                 info!("Peer identified: {}", peer_id);
             },
 
+            // Identify reports the observed address as a candidate, AutoNAT dials it back before it is used
+            SwarmEvent::ExternalAddrConfirmed { address } => {
+                info!("External address confirmed by AutoNAT: {}", address);
+            },
+            SwarmEvent::ExternalAddrExpired { address } => {
+                warn!("External address expired: {}", address);
+            },
+
             _ => {}
         }
     }